@@ -0,0 +1,34 @@
+//! Rendered fixtures for the benchmarks of the recognition
+
+#[allow(dead_code)]
+#[path = "../../src/fixtures.rs"]
+pub mod fixtures;
+
+use std::path::Path;
+
+/// The runs of each measure, the fastest one is reported
+pub const RUNS: usize = 3;
+
+/// Whether the models are installed
+pub fn has_models() -> bool {
+    ["assets/text-detection.rten", "assets/text-recognition.rten"]
+        .iter()
+        .all(|path| Path::new(path).exists())
+}
+
+/// Lines of prose for the fixtures, different from each other
+pub fn prose(count: usize, words: usize) -> Vec<String> {
+    const WORDS: &[&str] = &[
+        "the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog", "while", "seven",
+        "wizards", "quietly", "pack", "boxes", "with", "liquor", "jugs", "under", "amber",
+        "lights",
+    ];
+    (0..count)
+        .map(|line| {
+            let words: Vec<_> = (0..words)
+                .map(|word| WORDS[(line * 7 + word * 3) % WORDS.len()])
+                .collect();
+            format!("{line} {}", words.join(" "))
+        })
+        .collect()
+}
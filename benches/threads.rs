@@ -0,0 +1,76 @@
+//! Recognizes a prepared capture of many lines with more and more threads,
+//! each count in its own run of the benchmark since the engine reads it once
+
+mod common;
+
+use common::fixtures::{self, Style};
+use screen_ocr::ocr::{self, OcrOptions};
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+fn main() {
+    assert!(
+        common::has_models(),
+        "the benchmark needs the models in the assets directory"
+    );
+    let lines = common::prose(40, 12);
+    let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+
+    // the run measuring one thread count
+    let args: Vec<_> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--threads") {
+        let (time, read) = measure(&lines, args[index + 1].parse().unwrap());
+        println!("{} {read}", time.as_secs_f64());
+        return;
+    }
+
+    let cores = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut counts = vec![1];
+    while counts[counts.len() - 1] * 2 < cores {
+        counts.push(counts[counts.len() - 1] * 2);
+    }
+    if cores > 1 {
+        counts.push(cores);
+    }
+    println!("{} lines recognized on up to {cores} threads", lines.len());
+    let mut single = None;
+    for threads in counts {
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args(["--threads", &threads.to_string()])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let output = String::from_utf8(output.stdout).unwrap();
+        let (time, read) = output.trim().split_once(' ').unwrap();
+        let time = Duration::from_secs_f64(time.parse().unwrap());
+        let speedup = single.get_or_insert(time).as_secs_f64() / time.as_secs_f64();
+        println!(
+            "{threads:>3} threads: {time:>10.2?}, {speedup:.2}x, {read}/{} lines read exactly",
+            lines.len()
+        );
+    }
+}
+
+// The fastest recognition of the prepared fixture and the lines read exactly
+fn measure(lines: &[&str], threads: usize) -> (Duration, usize) {
+    ocr::set_threads(threads);
+    let image = fixtures::render(lines, Style::default());
+    let options = OcrOptions::default();
+    let prepared = ocr::prepare(image.as_raw(), image.dimensions(), &options).unwrap();
+    // the first run loads the models
+    let mut result = ocr::recognize_prepared(&prepared, &options).unwrap();
+    let mut best = Duration::MAX;
+    for _ in 0..common::RUNS {
+        let start = Instant::now();
+        result = ocr::recognize_prepared(&prepared, &options).unwrap();
+        best = best.min(start.elapsed());
+    }
+    let text = result.text();
+    let read = text.lines().filter(|line| lines.contains(line)).count();
+    (best, read)
+}
@@ -0,0 +1,46 @@
+//! Reads a capture taller and wider than a tile in a single pass and in
+//! tiles of several sizes
+
+mod common;
+
+use common::fixtures::{self, Style};
+use screen_ocr::ocr::{self, OcrOptions};
+
+use std::time::{Duration, Instant};
+
+fn main() {
+    assert!(
+        common::has_models(),
+        "the benchmark needs the models in the assets directory"
+    );
+    let lines = common::prose(80, 30);
+    let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+    let image = fixtures::render(&lines, Style::default());
+    println!("capture of {}x{} pixels", image.width(), image.height());
+
+    for (name, tile_size) in [
+        ("single pass", 0),
+        ("tiles of 2048", 2048),
+        ("tiles of 1024", 1024),
+    ] {
+        let options = OcrOptions {
+            tile_size,
+            ..OcrOptions::default()
+        };
+        let prepared = ocr::prepare(image.as_raw(), image.dimensions(), &options).unwrap();
+        // the first run loads the models
+        let mut result = ocr::recognize_prepared(&prepared, &options).unwrap();
+        let mut best = Duration::MAX;
+        for _ in 0..common::RUNS {
+            let start = Instant::now();
+            result = ocr::recognize_prepared(&prepared, &options).unwrap();
+            best = best.min(start.elapsed());
+        }
+        let text = result.text();
+        let read = text.lines().filter(|line| lines.contains(line)).count();
+        println!(
+            "{name:>14}: {best:>10.2?}, {read}/{} lines read exactly",
+            lines.len()
+        );
+    }
+}
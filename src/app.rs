@@ -4,6 +4,7 @@ use crate::state::*;
 
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use vello::kurbo::Point;
 use vello::peniko::Color;
 use vello::util::{RenderContext, RenderSurface};
 use vello::{AaConfig, Renderer, RendererOptions, Scene};
@@ -11,6 +12,7 @@ use winit::application::ApplicationHandler;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::*;
 use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::keyboard::{Key, NamedKey};
 use winit::window::{Window, WindowLevel};
 
 #[cfg(target_os = "windows")]
@@ -24,6 +26,14 @@ use vello::wgpu;
 const OVERLAY_WINDOW: usize = 0;
 const EXTRACT_WINDOW: usize = 1;
 
+#[derive(Debug, Clone)]
+pub enum UserEvent {
+    // Start a new capture from the idle state
+    Capture,
+    // Start a capture if none is running and reply with its result
+    Select(ResultSender),
+}
+
 pub struct App<'s> {
     // The vello RenderContext which is a global context that lasts for the
     // lifetime of the application
@@ -37,6 +47,8 @@ pub struct App<'s> {
     pub view: View,
     pub state: AppState,
     pub callbacks: Vec<fn(&mut AppState, &mut View, usize)>,
+    // stay idle in the background instead of exiting after a capture
+    pub resident: bool,
 }
 
 impl<'s> App<'s> {
@@ -44,12 +56,52 @@ impl<'s> App<'s> {
     fn callback(&self, index: usize) -> fn(&mut AppState, &mut View, usize) {
         match self.state.page {
             Page::AreaSelect => self.callbacks[index],
-            Page::TextExtract => self.callbacks[0],
+            Page::TextExtract => self
+                .callbacks
+                .get(index)
+                .copied()
+                .unwrap_or(self.callbacks[0]),
         }
     }
+
+    // Reset the state and show the overlay for a new area selection
+    fn start_capture(&mut self) {
+        self.close_extract_window();
+        let size = self.windows[OVERLAY_WINDOW].inner_size();
+        self.state.page = Page::AreaSelect;
+        *self.state.page_data = PageData::default();
+        self.state.screen_width = size.width as f64;
+        self.state.screen_height = size.height as f64;
+        self.state.damaged = true;
+        self.active = OVERLAY_WINDOW;
+        self.windows[OVERLAY_WINDOW].set_visible(true);
+        self.windows[OVERLAY_WINDOW].request_redraw();
+    }
+
+    // Exit, or go back to idle when running in the background
+    fn finish_capture(&mut self, event_loop: &ActiveEventLoop) {
+        for reply in self.state.pending_replies.drain(..) {
+            let _ = reply.send(Err("the capture was cancelled".to_string()));
+        }
+        if !self.resident {
+            event_loop.exit();
+            return;
+        }
+        self.close_extract_window();
+        self.windows[OVERLAY_WINDOW].set_visible(false);
+        self.active = OVERLAY_WINDOW;
+        self.state.page = Page::AreaSelect;
+        *self.state.page_data = PageData::default();
+    }
+
+    fn close_extract_window(&mut self) {
+        // the surface is dropped before its window
+        self.surfaces.truncate(EXTRACT_WINDOW);
+        self.windows.truncate(EXTRACT_WINDOW);
+    }
 }
 
-impl<'s> ApplicationHandler for App<'s> {
+impl<'s> ApplicationHandler<UserEvent> for App<'s> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Get the winit window cached in a previous Suspended event or else create a new window
         let window = Arc::new(create_overlay_window(event_loop));
@@ -65,7 +117,7 @@ impl<'s> ApplicationHandler for App<'s> {
         let size = window.inner_size();
         self.state.screen_width = size.width as f64;
         self.state.screen_height = size.height as f64;
-        window.set_visible(true);
+        window.set_visible(!self.resident);
 
         // Push the Window and Surface to App
         self.windows.push(window.clone());
@@ -78,6 +130,24 @@ impl<'s> ApplicationHandler for App<'s> {
         self.state.damaged = true;
     }
 
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        if self.windows.is_empty() {
+            return;
+        }
+        match event {
+            UserEvent::Capture => self.start_capture(),
+            UserEvent::Select(reply) => {
+                self.state.pending_replies.push(reply);
+                // join the selection in progress
+                let selecting = self.state.page == Page::AreaSelect
+                    && self.windows[OVERLAY_WINDOW].is_visible() == Some(true);
+                if !selecting {
+                    self.start_capture();
+                }
+            }
+        }
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -108,7 +178,10 @@ impl<'s> ApplicationHandler for App<'s> {
                     page_data.rect.height().abs() as u32,
                 );
                 let img_blob = page_data.blob.clone();
-                std::thread::spawn(move || extract_text(img_blob, dim));
+                let origin = Point::new(page_data.rect.min_x(), page_data.rect.min_y());
+                let time = page_data.time;
+                let options = self.state.settings.ocr_options();
+                std::thread::spawn(move || extract_text(img_blob, dim, origin, time, options));
                 // Create a new window
                 self.windows[OVERLAY_WINDOW].set_visible(false);
                 let window = Arc::new(create_main_window(event_loop));
@@ -123,17 +196,22 @@ impl<'s> ApplicationHandler for App<'s> {
         let surface = &mut self.surfaces[self.active];
 
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => self.finish_capture(event_loop),
+
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state.is_pressed()
+                    && event.logical_key == Key::Named(NamedKey::Escape) =>
+            {
+                self.finish_capture(event_loop)
+            }
 
             // Resize the surface when the window is resized
-            WindowEvent::Resized(size) => {
-                if size != Default::default() {
-                    self.state.screen_width = size.width as f64;
-                    self.state.screen_height = size.height as f64;
-                    self.context
-                        .resize_surface(surface, size.width, size.height);
-                    self.windows[self.active].request_redraw();
-                }
+            WindowEvent::Resized(size) if size != Default::default() => {
+                self.state.screen_width = size.width as f64;
+                self.state.screen_height = size.height as f64;
+                self.context
+                    .resize_surface(surface, size.width, size.height);
+                self.windows[self.active].request_redraw();
             }
 
             WindowEvent::RedrawRequested => {
@@ -278,10 +356,7 @@ fn create_vello_renderer(render_cx: &RenderContext, surface: &RenderSurface) ->
     .expect("Couldn't create renderer")
 }
 
-fn create_vello_surface<'s, 'c>(
-    window: Arc<Window>,
-    context: &'c mut RenderContext,
-) -> RenderSurface<'s> {
+fn create_vello_surface<'s>(window: Arc<Window>, context: &mut RenderContext) -> RenderSurface<'s> {
     // Create a vello Surface
     let size = window.inner_size();
     let surface_future = context.create_surface(
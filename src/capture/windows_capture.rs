@@ -4,6 +4,26 @@ use vello::kurbo::Rect;
 use vello::peniko::Blob;
 use windows::Win32::{Foundation::*, Graphics::Gdi::*};
 
+/// The screen device context is taken for each capture
+pub struct Capturer;
+
+impl Capturer {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn capture(&self, rect: Rect) -> Result<Blob<u8>> {
+        screen_rect(rect)
+    }
+
+    /// The rectangle covering the primary screen
+    pub fn bounds(&self) -> Rect {
+        use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+        let (w, h) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+        Rect::new(0.0, 0.0, w as f64, h as f64)
+    }
+}
+
 pub fn screen_rect(rect: Rect) -> Result<Blob<u8>> {
     unsafe {
         // Get the device context handle of the screen
@@ -1,24 +1,94 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::sync::Arc;
 use vello::kurbo::Rect;
 use vello::peniko::Blob;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto;
+use x11rb::rust_connection::RustConnection;
 use xproto::ImageFormat;
 
+/// Keeps the connection to the X server open between captures
+pub struct Capturer {
+    conn: RustConnection,
+    root: xproto::Window,
+}
+
+impl Capturer {
+    pub fn new() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self { conn, root })
+    }
+
+    pub fn capture(&self, rect: Rect) -> Result<Blob<u8>> {
+        let (x, y, w, h) = (
+            rect.min_x() as i16,
+            rect.min_y() as i16,
+            rect.width().abs() as u16,
+            rect.height().abs() as u16,
+        );
+        let reply = xproto::get_image(
+            &self.conn,
+            ImageFormat::Z_PIXMAP,
+            self.root,
+            x,
+            y,
+            w,
+            h,
+            u32::MAX,
+        )?
+        .reply()?;
+
+        if reply.data.len() != w as usize * h as usize * 4
+            || self.conn.setup().image_byte_order != xproto::ImageOrder::LSB_FIRST
+        {
+            bail!("unsupported screen format of depth {}", reply.depth);
+        }
+        let mut data = reply.data.into_boxed_slice();
+        bgrx_to_rgba(&mut data);
+        Ok(Blob::new(Arc::new(data)))
+    }
+
+    /// The rectangle covering the whole root window
+    pub fn bounds(&self) -> Rect {
+        let screen = self
+            .conn
+            .setup()
+            .roots
+            .iter()
+            .find(|screen| screen.root == self.root)
+            .unwrap();
+        Rect::new(
+            0.0,
+            0.0,
+            screen.width_in_pixels as f64,
+            screen.height_in_pixels as f64,
+        )
+    }
+}
+
+// The pixels of a 24 bit screen are BGRX, the rest of the application
+// expects RGBA like the Windows capture gives
+fn bgrx_to_rgba(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let [b, g, r, _] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        pixel.copy_from_slice(&[r, g, b, 255]);
+    }
+}
+
 pub fn screen_rect(rect: Rect) -> Result<Blob<u8>> {
-    let (conn, screen_num) = x11rb::connect(None)?;
-    let screen = &conn.setup().roots[screen_num];
-    let root = screen.root;
-    let (x, y, w, h) = (
-        rect.min_x() as i16,
-        rect.min_y() as i16,
-        rect.width().abs() as u16,
-        rect.height().abs() as u16,
-    );
-    let reply =
-        xproto::get_image(&conn, ImageFormat::Z_PIXMAP, root, x, y, w, h, u32::MAX)?.reply()?;
-
-    let data = reply.data.into_boxed_slice();
-    Ok(Blob::new(Arc::new(data)))
+    Capturer::new()?.capture(rect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba_pixels() {
+        // a red then a blue pixel as the X server sends them
+        let mut data = [0, 0, 255, 0, 255, 0, 0, 0];
+        bgrx_to_rgba(&mut data);
+        assert_eq!(data, [255, 0, 0, 255, 0, 0, 255, 255]);
+    }
 }
@@ -0,0 +1,368 @@
+use crate::clipboard::{ClipboardTarget, CopyMode, Selection};
+use crate::hotkey::Hotkey;
+use crate::layout::table::TableFormat;
+use crate::layout::TextMode;
+use crate::numbers::NumberLocale;
+use crate::ocr::OcrOptions;
+use crate::preprocess::{parse_color, Invert, Rotation, Step};
+use crate::subtitles::SubtitleFormat;
+
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::path::PathBuf;
+use vello::kurbo::Rect;
+
+/// Select an area of the screen and extract its text
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub settings: Settings,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Own the clipboard selections with the contents read from stdin
+    #[command(hide = true)]
+    ClipboardServe {
+        #[arg(long, value_delimiter = ',', required = true)]
+        selection: Vec<Selection>,
+
+        #[arg(long)]
+        clear_after: Option<humantime::Duration>,
+    },
+
+    /// Restore the clipboard contents replaced by the last copy
+    UndoCopy,
+
+    /// Stay in the background and start a capture when the hotkey is pressed
+    Daemon {
+        /// The global hotkey, e.g. "ctrl+alt+o", "super+shift+t" or "print"
+        #[arg(long, default_value = "ctrl+alt+o")]
+        hotkey: Hotkey,
+    },
+
+    /// Extract the text of a region of the screen
+    Region {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Extract the text of an image file
+    File {
+        path: PathBuf,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+
+    /// Print the new lines of text each time a region of the screen changes
+    Watch {
+        #[command(flatten)]
+        watch: WatchArgs,
+
+        /// Append the lines to this file instead of printing them
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Write the text as timed subtitle cues to this file
+        #[arg(long)]
+        subtitles: Option<PathBuf>,
+
+        /// The subtitle format, guessed from the file extension by default
+        #[arg(long, value_enum)]
+        subtitle_format: Option<SubtitleFormat>,
+    },
+
+    /// Run a command when the text of a region matches a pattern
+    Trigger {
+        #[command(flatten)]
+        watch: WatchArgs,
+
+        #[command(flatten)]
+        rule: TriggerArgs,
+    },
+
+    /// Click the words matching a text or a pattern
+    Click {
+        #[command(flatten)]
+        locate: LocateArgs,
+
+        #[command(flatten)]
+        click: ClickArgs,
+    },
+
+    /// Wait until a text appears in a region, fails after the timeout
+    WaitFor {
+        /// The expected text, or a regular expression with --regex
+        text: String,
+
+        /// Search a regular expression instead of the text
+        #[arg(long)]
+        regex: bool,
+
+        /// How long to wait for the text
+        #[arg(long, default_value = "30s")]
+        timeout: humantime::Duration,
+
+        #[command(flatten)]
+        watch: WatchArgs,
+    },
+
+    /// Append the numbers of a region to a CSV file at each capture
+    LogValues {
+        #[command(flatten)]
+        watch: WatchArgs,
+
+        /// The CSV file receiving the timestamped values
+        #[arg(long)]
+        csv: PathBuf,
+
+        /// The decimal and thousands separators of the numbers
+        #[arg(long, value_enum, default_value_t)]
+        locale: NumberLocale,
+    },
+
+    /// Print the screen boxes of the words matching a text or a pattern
+    Locate {
+        #[command(flatten)]
+        locate: LocateArgs,
+
+        /// Print the boxes as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Send a request to the daemon and print its JSON response
+    ///
+    /// The requests are `select`, `region X Y WIDTH HEIGHT`, `file PATH` and `last-result`
+    Client {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        request: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct Settings {
+    /// The selections that receive the extracted text
+    #[arg(long, value_enum, default_value_t)]
+    pub clipboard: ClipboardTarget,
+
+    /// Copy the text as soon as it is extracted or only with the copy button
+    #[arg(long, value_enum, default_value_t)]
+    pub copy: CopyMode,
+
+    /// Clear the copied text from the selections after this delay (e.g. "30s")
+    #[arg(long, value_name = "DURATION")]
+    pub clear_after: Option<humantime::Duration>,
+
+    /// How the words are turned into text, the extract window can change it
+    #[arg(long, value_enum, default_value_t)]
+    pub text_mode: TextMode,
+
+    /// Invert the colours of the captures with light text on a dark background
+    #[arg(long, value_enum, default_value_t)]
+    pub invert: Invert,
+
+    /// The preprocessing steps run on the captures before the recognition,
+    /// among grayscale, stretch, gamma[=G], sharpen, otsu and sauvola[=WINDOW]
+    #[arg(long, value_delimiter = ',', value_name = "STEPS")]
+    pub preprocess: Vec<Step>,
+
+    /// Upscale the lines of text smaller than this height in pixels before
+    /// reading them, 0 never upscales
+    #[arg(long, default_value_t = 16.0, value_name = "PIXELS")]
+    pub upscale_below: f64,
+
+    /// Turn the captures clockwise before the recognition, or try the
+    /// orientations with "auto"
+    #[arg(long, value_enum, default_value_t)]
+    pub rotate: Rotation,
+
+    /// Keep the captures with slanted text as they are
+    #[arg(long)]
+    pub no_deskew: bool,
+
+    /// Keep only the text of this color, as "RRGGBB"; the extract window
+    /// can pick it from the capture
+    #[arg(long, value_parser = parse_color, value_name = "COLOR")]
+    pub text_color: Option<[u8; 3]>,
+
+    /// How far the colors of the text can be from --text-color, from 0 to 441
+    #[arg(long, default_value_t = 60.0)]
+    pub color_tolerance: f64,
+
+    /// Crop the margins without text from the captures
+    #[arg(long)]
+    pub trim: bool,
+
+    /// The margin in pixels kept around the text by --trim
+    #[arg(long, default_value_t = 8, value_name = "PIXELS")]
+    pub trim_padding: u32,
+
+    /// Read the captures wider or taller than this in tiles, on the
+    /// recognition threads; 0 reads them whole
+    #[arg(long, default_value_t = 2048, value_name = "PIXELS")]
+    pub tile_size: u32,
+
+    /// The threads running the recognition, 0 uses one per physical core
+    #[arg(long, default_value_t = 0)]
+    pub threads: usize,
+
+    /// The format of the tables copied with the copy as table button
+    #[arg(long, value_enum, default_value_t)]
+    pub table_format: TableFormat,
+}
+
+/// How the extracted text is printed
+#[derive(Args, Debug, Clone, Default)]
+pub struct OutputArgs {
+    /// Print the words as a table in the --table-format format
+    #[arg(long)]
+    pub table: bool,
+}
+
+/// How a region of the screen is captured again and again
+#[derive(Args, Debug, Clone)]
+pub struct WatchArgs {
+    /// The watched region, as "X,Y,WIDTH,HEIGHT"
+    #[arg(long, value_parser = parse_region, required_unless_present = "frames")]
+    pub region: Option<Rect>,
+
+    /// Replay the images of this directory, in name order, instead of
+    /// capturing the screen; the region is cropped from them when given
+    #[arg(long, value_name = "DIR")]
+    pub frames: Option<PathBuf>,
+
+    /// The delay between two captures
+    #[arg(long, default_value = "1s")]
+    pub interval: humantime::Duration,
+
+    /// The mean pixel difference between two captures, from 0 to 1, that
+    /// makes the region recognized again
+    #[arg(long, default_value_t = 0.01)]
+    pub threshold: f64,
+}
+
+/// A rule firing a command on the matches of a pattern
+#[derive(Args, Debug, Clone)]
+pub struct TriggerArgs {
+    /// The regular expression searched in the text of the region
+    #[arg(long)]
+    pub pattern: Regex,
+
+    /// The shell command to run, the match and its groups are in the
+    /// SCREEN_OCR_MATCH and SCREEN_OCR_GROUP_<N> variables, and on unix also
+    /// its arguments
+    #[arg(long, value_name = "COMMAND")]
+    pub exec: String,
+
+    /// How long a match must stay on screen before the command runs
+    #[arg(long, default_value = "0s")]
+    pub debounce: humantime::Duration,
+
+    /// Exit after running the command once
+    #[arg(long)]
+    pub once: bool,
+
+    /// Print the command instead of running it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// The words searched on the screen
+#[derive(Args, Debug, Clone)]
+pub struct LocateArgs {
+    /// The searched words, or a regular expression with --regex
+    pub query: String,
+
+    /// Search a regular expression instead of words
+    #[arg(long)]
+    pub regex: bool,
+
+    /// The searched region, as "X,Y,WIDTH,HEIGHT", the whole screen by default
+    #[arg(long, value_parser = parse_region)]
+    pub region: Option<Rect>,
+
+    /// Search an image file instead of the screen
+    #[arg(long, conflicts_with = "region")]
+    pub file: Option<PathBuf>,
+
+    /// The lowest similarity, from 0 to 1, of the words found to the query
+    #[arg(long, default_value_t = 0.8)]
+    pub threshold: f64,
+}
+
+/// Where and how the pointer clicks
+#[derive(Args, Debug, Clone)]
+pub struct ClickArgs {
+    /// Click the centre of the matched words or of their whole line
+    #[arg(long, value_enum, default_value_t)]
+    pub target: ClickTarget,
+
+    /// Move the click horizontally from the centre, in pixels
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub offset_x: f64,
+
+    /// Move the click vertically from the centre, in pixels
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub offset_y: f64,
+
+    /// Click twice
+    #[arg(long)]
+    pub double: bool,
+
+    /// Print where the click would be instead of clicking
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum ClickTarget {
+    #[default]
+    Match,
+    Line,
+}
+
+pub fn parse_region(s: &str) -> Result<Rect> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x, y, width, height] = values[..] else {
+        bail!("expected X,Y,WIDTH,HEIGHT");
+    };
+    if width <= 0.0 || height <= 0.0 {
+        bail!("the region is empty");
+    }
+    Ok(Rect::new(x, y, x + width, y + height))
+}
+
+impl Settings {
+    pub fn clear_after(&self) -> Option<std::time::Duration> {
+        self.clear_after.map(Into::into)
+    }
+
+    pub fn ocr_options(&self) -> OcrOptions {
+        OcrOptions {
+            invert: self.invert,
+            preprocess: self.preprocess.clone(),
+            upscale_below: self.upscale_below,
+            deskew: !self.no_deskew,
+            rotation: self.rotate,
+            text_color: self.text_color,
+            color_tolerance: self.color_tolerance,
+            trim: self.trim.then_some(self.trim_padding),
+            tile_size: self.tile_size,
+        }
+    }
+}
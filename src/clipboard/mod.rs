@@ -0,0 +1,247 @@
+#[cfg(target_os = "windows")]
+mod windows_clipboard;
+#[cfg(target_os = "windows")]
+pub use windows_clipboard::*;
+
+#[cfg(target_os = "linux")]
+mod x11_clipboard;
+#[cfg(target_os = "linux")]
+pub use x11_clipboard::*;
+
+use crate::dirs;
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// the targets used to offer plain text to the other clients
+const TEXT_TARGETS: [&str; 4] = ["UTF8_STRING", "text/plain;charset=utf-8", "STRING", "TEXT"];
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardTarget {
+    #[default]
+    Clipboard,
+    Primary,
+    Both,
+    None,
+}
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum CopyMode {
+    #[default]
+    Auto,
+    Manual,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+impl ClipboardTarget {
+    pub fn selections(self) -> &'static [Selection] {
+        match self {
+            Self::Clipboard => &[Selection::Clipboard],
+            Self::Primary => &[Selection::Primary],
+            Self::Both => &[Selection::Clipboard, Selection::Primary],
+            Self::None => &[],
+        }
+    }
+}
+
+impl Selection {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Clipboard => "clipboard",
+            Self::Primary => "primary",
+        }
+    }
+
+    // the file keeping the contents replaced by the last copy
+    fn previous_path(self) -> Result<PathBuf> {
+        Ok(dirs::runtime_dir()?.join(format!("previous-{}", self.name())))
+    }
+}
+
+/// The data offered for a selection, as a list of targets and their bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contents {
+    pub entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Contents {
+    pub fn text(text: &str) -> Self {
+        let entries = TEXT_TARGETS
+            .iter()
+            .map(|target| (target.to_string(), text.as_bytes().to_vec()))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn text_data(&self) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(target, _)| TEXT_TARGETS.contains(&target.as_str()))
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        for (target, data) in &self.entries {
+            writer.write_all(&(target.len() as u32).to_le_bytes())?;
+            writer.write_all(target.as_bytes())?;
+            writer.write_all(&(data.len() as u64).to_le_bytes())?;
+            writer.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self> {
+        let mut entries = vec![];
+        loop {
+            let mut len = [0; 4];
+            match reader.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            }
+            let mut target = vec![0; u32::from_le_bytes(len) as usize];
+            reader.read_exact(&mut target)?;
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            let mut data = vec![0; u64::from_le_bytes(len) as usize];
+            reader.read_exact(&mut data)?;
+            entries.push((String::from_utf8(target)?, data));
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// Copy the text to the selections of the target, after saving their
+/// previous contents for [`restore_previous`]
+pub fn copy_text(target: ClipboardTarget, text: &str, clear_after: Option<Duration>) -> Result<()> {
+    for &selection in target.selections() {
+        if let Err(err) = save_previous(selection, text) {
+            eprintln!(
+                "warning: cannot save the previous {} contents: {err:#}",
+                selection.name()
+            );
+        }
+    }
+    copy(target.selections(), &Contents::text(text), clear_after)
+}
+
+/// Put back the contents saved by the last copy of every selection
+pub fn restore_previous() -> Result<()> {
+    let mut restored = false;
+    for selection in [Selection::Clipboard, Selection::Primary] {
+        let path = selection.previous_path()?;
+        let Ok(file) = std::fs::File::open(&path) else {
+            continue;
+        };
+        let contents = Contents::read_from(&mut BufReader::new(file))?;
+        copy(&[selection], &contents, None)?;
+        std::fs::remove_file(path)?;
+        restored = true;
+    }
+    if !restored {
+        bail!("there is no previous clipboard contents to restore");
+    }
+    Ok(())
+}
+
+fn save_previous(selection: Selection, text: &str) -> Result<()> {
+    let previous = read(selection)?;
+    // copying the same text again must not lose the contents before the first copy
+    if previous.text_data() == Some(text.as_bytes()) {
+        return Ok(());
+    }
+    let path = selection.previous_path()?;
+    if previous.entries.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // the file holds clipboard contents, keep it private
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // the mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = std::io::BufWriter::new(options.open(path)?);
+    previous.write_to(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Hand the contents to a detached `clipboard-serve` process, so the
+/// selections stay available after the application exits
+pub fn copy(
+    selections: &[Selection],
+    contents: &Contents,
+    clear_after: Option<Duration>,
+) -> Result<()> {
+    if selections.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<_> = selections.iter().map(|s| s.name()).collect();
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("clipboard-serve")
+        .arg("--selection")
+        .arg(names.join(","))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if let Some(timeout) = clear_after {
+        command
+            .arg("--clear-after")
+            .arg(humantime::format_duration(timeout).to_string());
+    }
+
+    // keep the owner alive when the terminal interrupts the application
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command
+        .spawn()
+        .context("cannot start the clipboard owner")?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    contents.write_to(&mut stdin)?;
+    drop(stdin);
+
+    // wait until the child owns the selections
+    let mut ready = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut ready)?;
+    // reap the child when it loses the selections
+    std::thread::spawn(move || child.wait());
+    if ready.trim() != "ready" {
+        bail!("the clipboard owner exited before taking the selections");
+    }
+    Ok(())
+}
+
+/// Entry point of the `clipboard-serve` command
+pub fn serve_stdin(selections: &[Selection], clear_after: Option<Duration>) -> Result<()> {
+    let contents = Contents::read_from(&mut std::io::stdin().lock())?;
+    serve(selections, &contents, clear_after, || {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "ready")?;
+        stdout.flush()?;
+        Ok(())
+    })
+}
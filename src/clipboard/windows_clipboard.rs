@@ -0,0 +1,48 @@
+use super::{Contents, Selection};
+
+use anyhow::{anyhow, Result};
+use copypasta::{ClipboardContext, ClipboardProvider};
+use std::time::Duration;
+
+/// Read the clipboard text, images and the PRIMARY selection are not supported
+pub fn read(selection: Selection) -> Result<Contents> {
+    if selection != Selection::Clipboard {
+        return Ok(Contents::default());
+    }
+    let mut ctx = ClipboardContext::new().map_err(|err| anyhow!("{err}"))?;
+    match ctx.get_contents() {
+        Ok(text) => Ok(Contents::text(&text)),
+        Err(_) => Ok(Contents::default()),
+    }
+}
+
+/// Set the clipboard text, the windows clipboard keeps it after the process
+/// exits and there is no PRIMARY selection to own
+pub fn serve(
+    selections: &[Selection],
+    contents: &Contents,
+    clear_after: Option<Duration>,
+    on_ready: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    if !selections.contains(&Selection::Clipboard) {
+        return on_ready();
+    }
+    let text = contents
+        .text_data()
+        .map(|data| String::from_utf8_lossy(data).into_owned())
+        .unwrap_or_default();
+    let mut ctx = ClipboardContext::new().map_err(|err| anyhow!("{err}"))?;
+    ctx.set_contents(text.clone())
+        .map_err(|err| anyhow!("{err}"))?;
+    on_ready()?;
+
+    if let Some(timeout) = clear_after {
+        std::thread::sleep(timeout);
+        // clear the clipboard only if nothing else was copied meanwhile
+        if ctx.get_contents().is_ok_and(|current| current == text) {
+            ctx.set_contents(String::new())
+                .map_err(|err| anyhow!("{err}"))?;
+        }
+    }
+    Ok(())
+}
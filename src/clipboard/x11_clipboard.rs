@@ -0,0 +1,331 @@
+use super::{Contents, Selection};
+
+use anyhow::{bail, Result};
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use xproto::{AtomEnum, CreateWindowAux, EventMask, PropMode, SelectionRequestEvent, WindowClass};
+
+// how long the selection owner has to answer a request
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        PRIMARY,
+        TARGETS,
+        INCR,
+        UTF8_STRING,
+        STRING,
+        SCREEN_OCR_SELECTION,
+    }
+}
+
+impl Atoms {
+    fn selection(&self, selection: Selection) -> xproto::Atom {
+        match selection {
+            Selection::Clipboard => self.CLIPBOARD,
+            Selection::Primary => self.PRIMARY,
+        }
+    }
+}
+
+/// Read the text and the image currently offered for the selection
+pub fn read(selection: Selection) -> Result<Contents> {
+    let (conn, window) = connect()?;
+    let atoms = Atoms::new(&conn)?.reply()?;
+    let selection = atoms.selection(selection);
+    if conn.get_selection_owner(selection)?.reply()?.owner == x11rb::NONE {
+        return Ok(Contents::default());
+    }
+
+    let Some(targets) = convert(&conn, &atoms, window, selection, atoms.TARGETS)? else {
+        return Ok(Contents::default());
+    };
+    let targets: Vec<_> = targets
+        .chunks_exact(4)
+        .map(|atom| u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+        .collect();
+
+    let mut image = None;
+    for &target in &targets {
+        let name = conn.get_atom_name(target)?.reply()?.name;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if name == "image/png" || (image.is_none() && name.starts_with("image/")) {
+            image = Some((target, name));
+        }
+    }
+    let text = [atoms.UTF8_STRING, atoms.STRING]
+        .into_iter()
+        .find(|target| targets.contains(target));
+
+    let mut contents = Contents::default();
+    if let Some(target) = text {
+        if let Some(data) = convert(&conn, &atoms, window, selection, target)? {
+            let text = String::from_utf8_lossy(&data).into_owned();
+            contents = Contents::text(&text);
+        }
+    }
+    if let Some((target, name)) = image {
+        if let Some(data) = convert(&conn, &atoms, window, selection, target)? {
+            contents.entries.push((name, data));
+        }
+    }
+    Ok(contents)
+}
+
+/// Own the selections and answer the requests of the other clients until
+/// every selection is taken by another owner or the timeout clears them
+pub fn serve(
+    selections: &[Selection],
+    contents: &Contents,
+    clear_after: Option<Duration>,
+    on_ready: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let (conn, window) = connect()?;
+    let atoms = Atoms::new(&conn)?.reply()?;
+
+    let mut targets = vec![];
+    for (target, data) in &contents.entries {
+        let atom = conn.intern_atom(false, target.as_bytes())?.reply()?.atom;
+        targets.push((atom, data.as_slice()));
+    }
+
+    for &selection in selections {
+        let atom = atoms.selection(selection);
+        conn.set_selection_owner(window, atom, x11rb::CURRENT_TIME)?;
+        if conn.get_selection_owner(atom)?.reply()?.owner != window {
+            bail!("cannot own the {} selection", selection.name());
+        }
+    }
+    on_ready()?;
+
+    // the server clears the selections of the windows of a closed connection
+    if let Some(timeout) = clear_after {
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            std::process::exit(0);
+        });
+    }
+
+    let mut owned = selections.len();
+    let mut transfers = vec![];
+    while owned > 0 || !transfers.is_empty() {
+        match conn.wait_for_event()? {
+            Event::SelectionRequest(request) => {
+                if let Some(transfer) = respond(&conn, &atoms, &targets, request)? {
+                    transfers.push(transfer);
+                }
+            }
+            Event::SelectionClear(_) => owned -= 1,
+            // the requestor deleted the property to ask for the next chunk
+            Event::PropertyNotify(event) if event.state == xproto::Property::DELETE => {
+                let Some(index) = transfers.iter().position(|transfer| {
+                    transfer.requestor == event.window && transfer.property == event.atom
+                }) else {
+                    continue;
+                };
+                if !transfers[index].send_chunk(&conn)? {
+                    transfers.swap_remove(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Data sent in chunks to a requestor after it deletes the property
+struct Transfer<'a> {
+    requestor: xproto::Window,
+    property: xproto::Atom,
+    target: xproto::Atom,
+    data: &'a [u8],
+    // the data are sent, only the empty chunk ending the transfer is left
+    done: bool,
+}
+
+impl Transfer<'_> {
+    // Send the next chunk and return whether the transfer goes on
+    fn send_chunk(&mut self, conn: &impl Connection) -> Result<bool> {
+        if self.done {
+            return Ok(false);
+        }
+        let size = self.data.len().min(chunk_size(conn));
+        let (chunk, rest) = self.data.split_at(size);
+        conn.change_property8(
+            PropMode::REPLACE,
+            self.requestor,
+            self.property,
+            self.target,
+            chunk,
+        )?;
+        conn.flush()?;
+        self.data = rest;
+        self.done = chunk.is_empty();
+        Ok(true)
+    }
+}
+
+// The bytes of selection data that fit in one request
+fn chunk_size(conn: &impl Connection) -> usize {
+    (conn.maximum_request_bytes() - 32).min(256 * 1024)
+}
+
+// Connect to the X server and create an invisible window to exchange the selections
+fn connect() -> Result<(RustConnection, xproto::Window)> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let screen = &conn.setup().roots[screen_num];
+    let window = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    Ok((conn, window))
+}
+
+// Ask the owner to convert the selection and return the data, following INCR transfers
+fn convert(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    window: xproto::Window,
+    selection: xproto::Atom,
+    target: xproto::Atom,
+) -> Result<Option<Vec<u8>>> {
+    let property = atoms.SCREEN_OCR_SELECTION;
+    conn.convert_selection(window, selection, target, property, x11rb::CURRENT_TIME)?;
+    conn.flush()?;
+
+    let deadline = Instant::now() + READ_TIMEOUT;
+    loop {
+        match poll_event(conn, deadline)? {
+            Event::SelectionNotify(event) if event.property == x11rb::NONE => return Ok(None),
+            Event::SelectionNotify(_) => break,
+            _ => {}
+        }
+    }
+
+    let reply = conn
+        .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX / 4)?
+        .reply()?;
+    if reply.type_ != atoms.INCR {
+        return Ok(Some(reply.value));
+    }
+
+    // the owner sends the data in chunks after each deletion of the property
+    conn.flush()?;
+    let mut data = vec![];
+    loop {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        match poll_event(conn, deadline)? {
+            Event::PropertyNotify(event)
+                if event.atom == property && event.state == xproto::Property::NEW_VALUE =>
+            {
+                let chunk = conn
+                    .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX / 4)?
+                    .reply()?;
+                conn.flush()?;
+                if chunk.value.is_empty() {
+                    return Ok(Some(data));
+                }
+                data.extend_from_slice(&chunk.value);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn poll_event(conn: &RustConnection, deadline: Instant) -> Result<Event> {
+    loop {
+        if let Some(event) = conn.poll_for_event()? {
+            return Ok(event);
+        }
+        if Instant::now() > deadline {
+            bail!("the selection owner did not answer");
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+// Answer a request, the data too big for one request start an INCR transfer
+fn respond<'a>(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    targets: &[(xproto::Atom, &'a [u8])],
+    request: SelectionRequestEvent,
+) -> Result<Option<Transfer<'a>>> {
+    // obsolete clients may not set the property
+    let property = if request.property == x11rb::NONE {
+        request.target
+    } else {
+        request.property
+    };
+    let requested = targets.iter().find(|(atom, _)| *atom == request.target);
+
+    let mut transfer = None;
+    let reply_property = if request.target == atoms.TARGETS {
+        let mut list = vec![atoms.TARGETS];
+        list.extend(targets.iter().map(|(atom, _)| *atom));
+        conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            AtomEnum::ATOM,
+            &list,
+        )?;
+        property
+    } else if let Some((atom, data)) = requested {
+        if data.len() > chunk_size(conn) {
+            // announce the size, the chunks follow the deletions of the property
+            let attributes =
+                xproto::ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+            conn.change_window_attributes(request.requestor, &attributes)?;
+            let size = u32::try_from(data.len()).unwrap_or(u32::MAX);
+            conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                atoms.INCR,
+                &[size],
+            )?;
+            transfer = Some(Transfer {
+                requestor: request.requestor,
+                property,
+                target: *atom,
+                data,
+                done: false,
+            });
+            property
+        } else {
+            conn.change_property8(PropMode::REPLACE, request.requestor, property, *atom, data)?;
+            property
+        }
+    } else {
+        x11rb::NONE
+    };
+
+    let notify = xproto::SelectionNotifyEvent {
+        response_type: xproto::SELECTION_NOTIFY_EVENT,
+        sequence: 0,
+        time: request.time,
+        requestor: request.requestor,
+        selection: request.selection,
+        target: request.target,
+        property: reply_property,
+    };
+    conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+    conn.flush()?;
+    Ok(transfer)
+}
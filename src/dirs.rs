@@ -0,0 +1,49 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+/// The directory of the runtime files shared by the instances of the application
+pub fn runtime_dir() -> Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("screen-ocr"),
+        None => {
+            let user = std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default();
+            std::env::temp_dir().join(format!("screen-ocr-{user}"))
+        }
+    };
+
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    // the directory holds clipboard contents, keep it private
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(&dir)?;
+    #[cfg(unix)]
+    check_private(&dir)?;
+    Ok(dir)
+}
+
+/// Refuses a directory another user could have created or can read, as the
+/// temporary directory fallback is shared by every user
+#[cfg(unix)]
+fn check_private(dir: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid {
+        bail!(
+            "{} is not a directory owned by the current user",
+            dir.display()
+        );
+    }
+    if metadata.mode() & 0o077 != 0 {
+        bail!("{} is accessible to other users", dir.display());
+    }
+    Ok(())
+}
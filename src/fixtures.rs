@@ -0,0 +1,235 @@
+//! Images of rendered text for the tests and the benchmarks
+
+use image::{Rgba, RgbaImage};
+use vello::kurbo::{flatten, BezPath, PathEl, Point};
+use vello::skrifa::instance::{LocationRef, Size};
+use vello::skrifa::outline::{DrawSettings, OutlinePen};
+use vello::skrifa::raw::FontRef;
+use vello::skrifa::MetadataProvider;
+
+const FONT: &[u8] = include_bytes!("../assets/Roboto-Regular.ttf");
+// the sub-scanlines sampled per row of pixels
+const SAMPLES: usize = 4;
+
+/// The colors and the font size of a fixture
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub size: f32,
+    // place the glyphs on a grid like a monospace font
+    pub monospace: bool,
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            size: 24.0,
+            monospace: false,
+            foreground: [0, 0, 0],
+            background: [255, 255, 255],
+        }
+    }
+}
+
+/// Render the lines of text below each other with a margin of one font size
+pub fn render(lines: &[&str], style: Style) -> RgbaImage {
+    let font = FontRef::new(FONT).unwrap();
+    let size = Size::new(style.size);
+    let charmap = font.charmap();
+    let metrics = font.glyph_metrics(size, LocationRef::default());
+    let outlines = font.outline_glyphs();
+    let margin = style.size as f64;
+    let line_height = style.size as f64 * 1.5;
+
+    let mut path = BezPath::new();
+    let mut width = 0f64;
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = margin + line_height * index as f64 + style.size as f64;
+        let mut x = margin;
+        for ch in line.chars() {
+            let gid = charmap.map(ch).unwrap_or_default();
+            let advance = metrics.advance_width(gid).unwrap_or_default() as f64;
+            let cell = match style.monospace {
+                true => style.size as f64 * 0.6,
+                false => advance,
+            };
+            if let Some(glyph) = outlines.get(gid) {
+                let mut pen = Pen {
+                    path: &mut path,
+                    origin: Point::new(x + (cell - advance) / 2.0, baseline),
+                };
+                let settings = DrawSettings::unhinted(size, LocationRef::default());
+                glyph.draw(settings, &mut pen).unwrap();
+            }
+            x += cell;
+        }
+        width = width.max(x);
+    }
+    let height = margin * 2.0 + line_height * lines.len().max(1) as f64;
+    let coverage = rasterize(&path, (width + margin) as usize, height as usize);
+
+    let mut image = RgbaImage::new((width + margin) as u32, height as u32);
+    for (pixel, coverage) in image.pixels_mut().zip(coverage) {
+        let coverage = coverage.min(1.0);
+        let mix = |channel: usize| {
+            let (fg, bg) = (style.foreground[channel], style.background[channel]);
+            (bg as f64 + (fg as f64 - bg as f64) * coverage).round() as u8
+        };
+        *pixel = Rgba([mix(0), mix(1), mix(2), 255]);
+    }
+    image
+}
+
+/// Render texts at the given positions, like the cells of a table or the
+/// columns of a page; the image has the margin around the texts
+pub fn render_at(texts: &[(u32, u32, &str)], style: Style) -> RgbaImage {
+    let rendered: Vec<_> = texts
+        .iter()
+        .map(|&(x, y, text)| (x, y, render(&[text], style)))
+        .collect();
+    let width = rendered.iter().map(|(x, _, image)| x + image.width());
+    let height = rendered.iter().map(|(_, y, image)| y + image.height());
+    let (width, height) = (width.max().unwrap_or(1), height.max().unwrap_or(1));
+    let [r, g, b] = style.background;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+    for (x, y, text) in rendered {
+        for (px, py, pixel) in text.enumerate_pixels() {
+            // the pixel furthest from the background wins where margins overlap
+            let target = image.get_pixel_mut(x + px, y + py);
+            let distance = |p: &Rgba<u8>| {
+                (0..3)
+                    .map(|c| p[c].abs_diff(style.background[c]) as u32)
+                    .sum::<u32>()
+            };
+            if distance(pixel) > distance(target) {
+                *target = *pixel;
+            }
+        }
+    }
+    image
+}
+
+struct Pen<'a> {
+    path: &'a mut BezPath,
+    origin: Point,
+}
+
+impl Pen<'_> {
+    // the font units go up from the baseline
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point::new(self.origin.x + x as f64, self.origin.y - y as f64)
+    }
+}
+
+impl OutlinePen for Pen<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.path.move_to(point);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.path.line_to(point);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        let (control, point) = (self.point(cx0, cy0), self.point(x, y));
+        self.path.quad_to(control, point);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        let (c0, c1, point) = (self.point(cx0, cy0), self.point(cx1, cy1), self.point(x, y));
+        self.path.curve_to(c0, c1, point);
+    }
+
+    fn close(&mut self) {
+        self.path.close_path();
+    }
+}
+
+// The coverage of the pixels by the path with the non-zero rule, sampled on
+// horizontal sub-scanlines with exact coverage along each of them
+fn rasterize(path: &BezPath, width: usize, height: usize) -> Vec<f64> {
+    let mut segments = Vec::new();
+    let (mut start, mut last) = (Point::ZERO, Point::ZERO);
+    flatten(path, 0.05, |element| match element {
+        PathEl::MoveTo(point) => (start, last) = (point, point),
+        PathEl::LineTo(point) => {
+            segments.push((last, point));
+            last = point;
+        }
+        PathEl::ClosePath => {
+            segments.push((last, start));
+            last = start;
+        }
+        _ => unreachable!("flattened paths only have lines"),
+    });
+
+    let mut coverage = vec![0f64; width * height];
+    let mut crossings = Vec::new();
+    for row in 0..height {
+        for sample in 0..SAMPLES {
+            let y = row as f64 + (sample as f64 + 0.5) / SAMPLES as f64;
+            crossings.clear();
+            for &(p0, p1) in &segments {
+                if (p0.y <= y) != (p1.y <= y) {
+                    let x = p0.x + (y - p0.y) / (p1.y - p0.y) * (p1.x - p0.x);
+                    crossings.push((x, if p1.y > p0.y { 1 } else { -1 }));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding != 0 {
+                    fill_span(&mut coverage[row * width..][..width], pair[0].0, pair[1].0);
+                }
+            }
+        }
+    }
+    coverage
+}
+
+fn fill_span(row: &mut [f64], x0: f64, x1: f64) {
+    let weight = 1.0 / SAMPLES as f64;
+    let (x0, x1) = (x0.max(0.0), x1.min(row.len() as f64));
+    let mut x = x0;
+    while x < x1 {
+        let pixel = x.floor();
+        let end = (pixel + 1.0).min(x1);
+        row[pixel as usize] += (end - x) * weight;
+        x = end;
+    }
+}
+
+/// A directory of frames showing each text, or nothing, on canvases of the
+/// same size
+pub fn frames(name: &str, texts: &[Option<&str>]) -> std::path::PathBuf {
+    let dir = temp_dir(name);
+    let rendered: Vec<_> = texts
+        .iter()
+        .map(|text| text.map(|text| render(&[text], Style::default())))
+        .collect();
+    let width = rendered.iter().flatten().map(|image| image.width()).max();
+    let height = rendered.iter().flatten().map(|image| image.height()).max();
+    let (width, height) = (width.unwrap_or(1), height.unwrap_or(1));
+    for (index, text) in rendered.iter().enumerate() {
+        let mut frame = RgbaImage::from_pixel(width, height, Rgba([255; 4]));
+        if let Some(text) = text {
+            image::imageops::overlay(&mut frame, text, 0, 0);
+        }
+        frame.save(dir.join(format!("{index:03}.png"))).unwrap();
+    }
+    dir
+}
+
+/// An empty directory for the files of a test
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("screen-ocr-test-{}-{name}", std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
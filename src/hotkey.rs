@@ -0,0 +1,146 @@
+use crate::app::UserEvent;
+
+use anyhow::{anyhow, bail, Result};
+use std::str::FromStr;
+use winit::event_loop::EventLoopProxy;
+
+#[cfg(target_os = "linux")]
+use x11rb::protocol::xproto::ModMask;
+
+/// A key combination like `ctrl+alt+o`, the key is stored as an X11 keysym
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+    pub keysym: u32,
+}
+
+impl FromStr for Hotkey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut hotkey = Hotkey {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            keysym: 0,
+        };
+        let mut parts: Vec<_> = s.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let key = parts.pop().unwrap_or_default();
+        for modifier in parts {
+            match modifier.as_str() {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "alt" | "mod1" => hotkey.alt = true,
+                "shift" => hotkey.shift = true,
+                "super" | "logo" | "win" | "mod4" => hotkey.logo = true,
+                _ => bail!("unknown modifier `{modifier}`"),
+            }
+        }
+        hotkey.keysym = match key.as_str() {
+            "space" => 0x20,
+            "print" => 0xff61,
+            "pause" => 0xff13,
+            "insert" => 0xff63,
+            "scrolllock" => 0xff14,
+            key if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                key.as_bytes()[0] as u32
+            }
+            key => {
+                // the keysyms of the function keys follow each other
+                let n = key
+                    .strip_prefix('f')
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .filter(|n| (1..=24).contains(n))
+                    .ok_or_else(|| anyhow!("unknown key `{key}`"))?;
+                0xffbe + n - 1
+            }
+        };
+        Ok(hotkey)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Hotkey {
+    fn modifiers(&self) -> ModMask {
+        let mut mask = ModMask::from(0u16);
+        for (on, modifier) in [
+            (self.ctrl, ModMask::CONTROL),
+            (self.alt, ModMask::M1),
+            (self.shift, ModMask::SHIFT),
+            (self.logo, ModMask::M4),
+        ] {
+            if on {
+                mask |= modifier;
+            }
+        }
+        mask
+    }
+}
+
+/// Grab the hotkey on the root window and send a capture event to the event
+/// loop each time it is pressed
+#[cfg(target_os = "linux")]
+pub fn listen(hotkey: Hotkey, proxy: EventLoopProxy<UserEvent>) -> Result<()> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt, GrabMode};
+    use x11rb::protocol::Event;
+
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let (min_keycode, max_keycode) = (conn.setup().min_keycode, conn.setup().max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
+    let per_keycode = mapping.keysyms_per_keycode as usize;
+    let keycode = mapping
+        .keysyms
+        .iter()
+        .position(|&keysym| keysym == hotkey.keysym)
+        .map(|index| min_keycode + (index / per_keycode) as u8)
+        .ok_or_else(|| anyhow!("the hotkey is not on the keyboard"))?;
+
+    // grab the key with the lock modifiers too, so caps lock and num lock don't disable it
+    let modifiers = hotkey.modifiers();
+    for lock in [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ] {
+        conn.grab_key(
+            true,
+            root,
+            modifiers | lock,
+            keycode,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+        )?
+        .check()
+        .map_err(|_| anyhow!("the hotkey is already grabbed by another application"))?;
+    }
+
+    std::thread::spawn(move || loop {
+        match conn.wait_for_event() {
+            Ok(Event::KeyPress(_)) => {
+                // stop listening when the event loop is closed
+                if proxy.send_event(UserEvent::Capture).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("error: the hotkey listener stopped: {err}");
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen(_hotkey: Hotkey, _proxy: EventLoopProxy<UserEvent>) -> Result<()> {
+    bail!("global hotkeys are only supported on X11")
+}
@@ -0,0 +1,366 @@
+use crate::app::UserEvent;
+use crate::dirs;
+use crate::ocr::{self, Line, OcrResult, Word};
+use crate::scenes::RotatedRect;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use vello::kurbo::Rect;
+use winit::event_loop::EventLoopProxy;
+
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// A command sent on the socket, one line per connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Select,
+    Region(Rect),
+    File(PathBuf),
+    LastResult,
+}
+
+/// The JSON line answering a request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub words: Vec<WordBox>,
+}
+
+/// A recognized word with its bounding box in screen coordinates
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WordBox {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl FromStr for Request {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "select" => Ok(Self::Select),
+            "last-result" => Ok(Self::LastResult),
+            "file" if !args.trim().is_empty() => Ok(Self::File(PathBuf::from(args.trim()))),
+            "region" => {
+                let values = args
+                    .split_whitespace()
+                    .map(f64::from_str)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let [x, y, w, h] = values[..] else {
+                    bail!("usage: region X Y WIDTH HEIGHT");
+                };
+                if w <= 0.0 || h <= 0.0 {
+                    bail!("the region is empty");
+                }
+                Ok(Self::Region(Rect::new(x, y, x + w, y + h)))
+            }
+            "file" => bail!("usage: file PATH"),
+            _ => bail!("unknown request `{command}`"),
+        }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Select => write!(f, "select"),
+            Self::LastResult => write!(f, "last-result"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Region(rect) => write!(
+                f,
+                "region {} {} {} {}",
+                rect.x0,
+                rect.y0,
+                rect.width(),
+                rect.height()
+            ),
+        }
+    }
+}
+
+impl From<&OcrResult> for Response {
+    fn from(result: &OcrResult) -> Self {
+        let words = result
+            .words()
+            .map(|word| {
+                let rect = result.screen_rect(word);
+                WordBox {
+                    text: word.text.clone(),
+                    x: rect.x0,
+                    y: rect.y0,
+                    width: rect.width(),
+                    height: rect.height(),
+                }
+            })
+            .collect();
+        Self {
+            ok: true,
+            error: None,
+            text: result.text(),
+            words,
+        }
+    }
+}
+
+impl From<&Response> for OcrResult {
+    // the words keep their screen boxes but the lines are lost, one line per word
+    fn from(response: &Response) -> Self {
+        let lines = response
+            .words
+            .iter()
+            .map(|word| Line {
+                words: vec![Word {
+                    text: word.text.clone(),
+                    rect: RotatedRect::from(Rect::new(
+                        word.x,
+                        word.y,
+                        word.x + word.width,
+                        word.y + word.height,
+                    )),
+                }],
+            })
+            .collect();
+        Self {
+            lines,
+            origin: Default::default(),
+        }
+    }
+}
+
+impl Response {
+    fn error(message: String) -> Self {
+        Self {
+            error: Some(message),
+            ..Default::default()
+        }
+    }
+}
+
+/// The lock held by the instance listening on the socket, the system
+/// releases it when the process exits so it never outlives its instance
+pub struct InstanceLock {
+    _file: File,
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(dirs::runtime_dir()?.join("socket"))
+}
+
+/// Take the instance lock, or return `None` when another instance holds it
+pub fn lock_instance() -> Result<Option<InstanceLock>> {
+    lock_instance_in(&dirs::runtime_dir()?)
+}
+
+fn lock_instance_in(dir: &Path) -> Result<Option<InstanceLock>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join("instance.lock"))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+/// Listen on the socket and execute the requests in the background
+pub fn serve(lock: InstanceLock, proxy: EventLoopProxy<UserEvent>) -> Result<()> {
+    // the selection is made in the window of the event loop
+    let select = move || {
+        let (sender, receiver) = mpsc::channel();
+        proxy
+            .send_event(UserEvent::Select(sender))
+            .map_err(|_| anyhow!("the application is closing"))?;
+        receiver.recv()?.map_err(|err| anyhow!(err))
+    };
+    serve_at(&socket_path()?, lock, select)
+}
+
+fn serve_at<S>(path: &Path, lock: InstanceLock, select: S) -> Result<()>
+where
+    S: Fn() -> Result<OcrResult> + Clone + Send + 'static,
+{
+    // holding the lock means the socket was left by an instance that did not exit cleanly
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+
+    std::thread::spawn(move || {
+        // keep the lock as long as the socket is served
+        let _lock = lock;
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let select = select.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = handle(stream, &select) {
+                    eprintln!("warning: cannot answer a request: {err:#}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut stream: UnixStream, select: &impl Fn() -> Result<OcrResult>) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response = line
+        .parse::<Request>()
+        .and_then(|request| execute(request, select))
+        .unwrap_or_else(|err| Response::error(format!("{err:#}")));
+    serde_json::to_writer(&mut stream, &response)?;
+    stream.write_all(b"\n")?;
+    Ok(())
+}
+
+fn execute(request: Request, select: &impl Fn() -> Result<OcrResult>) -> Result<Response> {
+    let result = match request {
+        Request::Select => select()?,
+        Request::Region(rect) => ocr::recognize_region(rect)?,
+        Request::File(path) => ocr::recognize_file(&path)?,
+        Request::LastResult => ocr::last_result().context("no text was extracted yet")?,
+    };
+    Ok(Response::from(&result))
+}
+
+/// Send a request to the running instance and wait for its response,
+/// `patience` is how long to wait for an instance that is still starting
+pub fn send(request: &Request, patience: Duration) -> Result<Response> {
+    send_to(&socket_path()?, request, patience)
+}
+
+fn send_to(path: &Path, request: &Request, patience: Duration) -> Result<Response> {
+    let deadline = Instant::now() + patience;
+    let mut stream = loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => break stream,
+            Err(_) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(err) => {
+                return Err(err).context("no running instance, start one with `screen-ocr daemon`")
+            }
+        }
+    };
+    writeln!(stream, "{request}")?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+
+    fn serve_temp(name: &str) -> PathBuf {
+        let dir = fixtures::temp_dir(name);
+        let lock = lock_instance_in(&dir).unwrap().unwrap();
+        let path = dir.join("socket");
+        serve_at(&path, lock, || bail!("no window to select in")).unwrap();
+        path
+    }
+
+    fn send_line(path: &Path, line: &str) -> Response {
+        let mut stream = UnixStream::connect(path).unwrap();
+        writeln!(stream, "{line}").unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn region_errors() {
+        let path = serve_temp("region");
+        for (line, error) in [
+            ("region 1 2 3", "usage: region X Y WIDTH HEIGHT"),
+            ("region 1 2 x 4", "invalid float literal"),
+            ("region 10 10 0 5", "the region is empty"),
+            ("frobnicate", "unknown request `frobnicate`"),
+        ] {
+            let response = send_line(&path, line);
+            assert!(!response.ok, "{line}");
+            assert_eq!(response.error.as_deref(), Some(error), "{line}");
+        }
+    }
+
+    #[test]
+    fn select_error() {
+        let path = serve_temp("select");
+        let response = send_to(&path, &Request::Select, Duration::ZERO).unwrap();
+        assert_eq!(response.error.as_deref(), Some("no window to select in"));
+    }
+
+    #[test]
+    fn last_result_and_missing_file() {
+        let dir = fixtures::temp_dir("missing");
+        let path = serve_temp("last-result");
+
+        let result = OcrResult::from_boxes(&[
+            &[
+                ("hello", Rect::new(10.0, 20.0, 60.0, 40.0)),
+                ("world", Rect::new(70.0, 20.0, 120.0, 40.0)),
+            ],
+            &[("again", Rect::new(10.0, 50.0, 60.0, 70.0))],
+        ]);
+        ocr::set_last_result(&result);
+        let response = send_to(&path, &Request::LastResult, Duration::ZERO).unwrap();
+        assert!(response.ok);
+        assert_eq!(response.text, "hello world\nagain");
+        let words: Vec<_> = response
+            .words
+            .iter()
+            .map(|word| (word.text.as_str(), word.x, word.y, word.width, word.height))
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("hello", 10.0, 20.0, 50.0, 20.0),
+                ("world", 70.0, 20.0, 50.0, 20.0),
+                ("again", 10.0, 50.0, 50.0, 20.0),
+            ]
+        );
+
+        let missing = dir.join("missing.png");
+        let response = send_to(&path, &Request::File(missing.clone()), Duration::ZERO).unwrap();
+        assert!(!response.ok);
+        let error = format!("cannot open {}", missing.display());
+        assert!(response.error.unwrap().starts_with(&error));
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn recognize_file() {
+        let dir = fixtures::temp_dir("hello");
+        let path = serve_temp("file");
+        let image = dir.join("hello.png");
+        fixtures::render(&["Hello world"], Style::default())
+            .save(&image)
+            .unwrap();
+        let response = send_to(&path, &Request::File(image), Duration::ZERO).unwrap();
+        assert!(response.ok, "{:?}", response.error);
+        assert_eq!(response.text, "Hello world");
+        assert_eq!(response.words.len(), 2);
+    }
+}
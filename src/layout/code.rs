@@ -0,0 +1,151 @@
+use super::{grid_text, rows, WordBox};
+use crate::ocr::OcrResult;
+
+/// Rebuild source code or terminal output on a monospace grid
+pub fn code_text(result: &OcrResult) -> String {
+    let rows: Vec<Vec<WordBox>> = rows(result);
+    // every character of a monospace font has the same width, the median of
+    // the words is not thrown off by the boxes of short words
+    let mut widths: Vec<f64> = rows
+        .iter()
+        .flatten()
+        .filter(|w| w.text.chars().count() > 1)
+        .map(|w| w.rect.width() / w.text.chars().count() as f64)
+        .collect();
+    if widths.is_empty() {
+        widths = rows.iter().flatten().map(|w| w.rect.width()).collect();
+    }
+    widths.sort_by(f64::total_cmp);
+    let Some(&char_width) = widths.get(widths.len() / 2) else {
+        return String::new();
+    };
+
+    let text = grid_text(&rows, char_width);
+    let lines: Vec<_> = text.lines().map(fix_code_line).collect();
+    lines.join("\n")
+}
+
+// Replace the characters the recognition confuses in code, identifiers and
+// operators mix letters, digits and bars so only the letters inside numbers
+// are changed
+fn fix_code_line(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut fixed = String::with_capacity(line.len());
+    let mut token = 0..0;
+    for (i, &c) in chars.iter().enumerate() {
+        if i >= token.end {
+            let end = chars[i..]
+                .iter()
+                .position(|c| !c.is_alphanumeric())
+                .map_or(chars.len(), |len| i + len.max(1));
+            token = i..end;
+        }
+        fixed.push(match c {
+            // the code editors use straight quotes
+            '‘' | '’' | '´' | '′' => '\'',
+            '“' | '”' | '″' => '"',
+            '–' | '—' => '-',
+            'O' | 'o' if inside_number(&chars[token.clone()], i - token.start) => '0',
+            'l' | 'I' if inside_number(&chars[token.clone()], i - token.start) => '1',
+            c => c,
+        });
+    }
+    fixed
+}
+
+// Whether the letter at the index is between the digits of a number, like
+// `1O0`, but not a suffix or the `0o` prefix of an octal number
+fn inside_number(token: &[char], index: usize) -> bool {
+    let is_digit_or_confused = |c: &char| c.is_ascii_digit() || matches!(c, 'O' | 'o' | 'l' | 'I');
+    index > 0
+        && index + 1 < token.len()
+        && !(index == 1 && token[0] == '0')
+        && token[index - 1].is_ascii_digit()
+        && token[index + 1].is_ascii_digit()
+        && token.iter().all(is_digit_or_confused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+    use crate::ocr;
+    use vello::kurbo::Rect;
+
+    #[test]
+    fn letters_inside_numbers() {
+        assert_eq!(fix_code_line("let x = 1O0 + 2l5;"), "let x = 100 + 215;");
+        assert_eq!(fix_code_line("n = 1I0O1"), "n = 11001");
+    }
+
+    #[test]
+    fn code_left_intact() {
+        for line in [
+            "flags = 1|2",
+            "sha1sum file | cut -d ' ' -f1",
+            "apiVersion: v1beta1",
+            "let long = 10l; let octal = 0o17;",
+            "for (int lO = 0; lO < 1O; lO++)",
+            "x0 = l1 + O2",
+        ] {
+            assert_eq!(fix_code_line(line), line);
+        }
+    }
+
+    #[test]
+    fn straight_quotes() {
+        assert_eq!(
+            fix_code_line("print(“a” + ‘b’) – 1"),
+            "print(\"a\" + 'b') - 1"
+        );
+    }
+
+    #[test]
+    fn monospace_grid() {
+        // 10 pixels per character, the second line is indented by 4
+        let word = |text: &'static str, column: f64, row: f64| {
+            let x = column * 10.0;
+            let rect = Rect::new(
+                x,
+                row * 20.0,
+                x + text.len() as f64 * 10.0,
+                row * 20.0 + 16.0,
+            );
+            (text, rect)
+        };
+        let result = ocr::OcrResult::from_boxes(&[
+            &[
+                word("fn", 0.0, 0.0),
+                word("main()", 3.0, 0.0),
+                word("{", 10.0, 0.0),
+            ],
+            &[
+                word("x", 4.0, 1.0),
+                word("=", 6.0, 1.0),
+                word("1|2;", 8.0, 1.0),
+            ],
+            &[word("}", 0.0, 2.0)],
+        ]);
+        assert_eq!(code_text(&result), "fn main() {\n    x = 1|2;\n}");
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn rendered_code() {
+        let lines = [
+            "fn main() {",
+            "    let flags = 1|2;",
+            "    sha1sum(v1beta1);",
+            "}",
+        ];
+        let style = Style {
+            size: 20.0,
+            monospace: true,
+            ..Style::default()
+        };
+        let image = fixtures::render(&lines, style);
+        let options = ocr::OcrOptions::default();
+        let result = ocr::recognize_with(image.as_raw(), image.dimensions(), &options).unwrap();
+        assert_eq!(code_text(&result), lines.join("\n"));
+    }
+}
@@ -0,0 +1,207 @@
+pub mod code;
+pub mod order;
+pub mod paragraphs;
+pub mod table;
+
+use crate::ocr::{OcrResult, Word};
+
+use clap::ValueEnum;
+use vello::kurbo::Rect;
+
+/// How the recognized words are turned into text
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum TextMode {
+    /// One line of words per recognized line
+    #[default]
+    Lines,
+    /// The lines of the columns of text one after the other, from left to
+    /// right
+    Columns,
+    /// Keep the indentation and alignment of the words with spaces
+    Layout,
+    /// Snap the words to a monospace grid and fix the characters code confuses
+    Code,
+    /// Join the lines of prose into paragraphs and the words hyphenated at
+    /// the end of the lines
+    Paragraphs,
+}
+
+impl TextMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lines => "lines",
+            Self::Columns => "columns",
+            Self::Layout => "layout",
+            Self::Code => "code",
+            Self::Paragraphs => "paragraphs",
+        }
+    }
+
+    /// The mode after this one, to cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Lines => Self::Columns,
+            Self::Columns => Self::Layout,
+            Self::Layout => Self::Code,
+            Self::Code => Self::Paragraphs,
+            Self::Paragraphs => Self::Lines,
+        }
+    }
+}
+
+/// The text of the result in the given mode
+pub fn text(result: &OcrResult, mode: TextMode) -> String {
+    match mode {
+        TextMode::Lines => result.text(),
+        TextMode::Columns => {
+            let blocks: Vec<_> = order::blocks(result)
+                .iter()
+                .map(|block| block.result.text())
+                .collect();
+            blocks.join("\n")
+        }
+        TextMode::Layout => {
+            let rows = rows(result);
+            let chars: usize = rows.iter().flatten().map(|w| w.text.chars().count()).sum();
+            let width: f64 = rows.iter().flatten().map(|w| w.rect.width()).sum();
+            grid_text(&rows, width / chars.max(1) as f64)
+        }
+        TextMode::Code => code::code_text(result),
+        TextMode::Paragraphs => {
+            let blocks: Vec<_> = order::blocks(result)
+                .iter()
+                .map(|block| paragraphs::paragraphs_text(&block.result))
+                .collect();
+            blocks.join("\n\n")
+        }
+    }
+}
+
+/// A recognized word with its upright bounding box in image coordinates
+#[derive(Debug, Clone)]
+pub struct WordBox<'a> {
+    pub text: &'a str,
+    pub rect: Rect,
+    pub word: &'a Word,
+}
+
+impl<'a> From<&'a Word> for WordBox<'a> {
+    fn from(word: &'a Word) -> Self {
+        Self {
+            text: &word.text,
+            rect: Rect::from(&word.rect),
+            word,
+        }
+    }
+}
+
+/// Group the words in rows of vertically overlapping boxes, from top to
+/// bottom, each row sorted from left to right
+pub fn rows(result: &OcrResult) -> Vec<Vec<WordBox<'_>>> {
+    group_rows(result.words().map(WordBox::from).collect())
+}
+
+/// Group the given words in rows like `rows`
+pub fn group_rows(mut words: Vec<WordBox>) -> Vec<Vec<WordBox>> {
+    words.sort_by(|a, b| a.rect.center().y.total_cmp(&b.rect.center().y));
+
+    let mut rows: Vec<(Rect, Vec<WordBox>)> = vec![];
+    for word in words {
+        match rows.last_mut() {
+            // a word belongs to the row when they overlap over half its height
+            Some((bound, row))
+                if overlap(bound.y0..bound.y1, word.rect.y0..word.rect.y1)
+                    > 0.5 * word.rect.height().min(bound.height()) =>
+            {
+                *bound = bound.union(word.rect);
+                row.push(word);
+            }
+            _ => rows.push((word.rect, vec![word])),
+        }
+    }
+    rows.into_iter()
+        .map(|(_, mut row)| {
+            row.sort_by(|a, b| a.rect.x0.total_cmp(&b.rect.x0));
+            row
+        })
+        .collect()
+}
+
+// The length shared by two intervals
+fn overlap(a: std::ops::Range<f64>, b: std::ops::Range<f64>) -> f64 {
+    (a.end.min(b.end) - a.start.max(b.start)).max(0.0)
+}
+
+/// Place the words of the rows on a grid of characters of the given width
+pub fn grid_text(rows: &[Vec<WordBox>], char_width: f64) -> String {
+    let words = rows.iter().flatten();
+    if rows.is_empty() || char_width <= 0.0 {
+        return String::new();
+    }
+    let left = words
+        .clone()
+        .map(|w| w.rect.x0)
+        .fold(f64::INFINITY, f64::min);
+    let mut heights: Vec<_> = words.map(|w| w.rect.height()).collect();
+    heights.sort_by(f64::total_cmp);
+    let line_height = heights[heights.len() / 2];
+
+    let mut lines = vec![];
+    let mut bottom: Option<f64> = None;
+    for row in rows {
+        let top = row.iter().map(|w| w.rect.y0).fold(f64::INFINITY, f64::min);
+        // the empty lines between two rows far apart
+        if let Some(bottom) = bottom {
+            let blank = ((top - bottom) / line_height).floor().max(0.0) as usize;
+            lines.extend(std::iter::repeat_n(String::new(), blank));
+        }
+        bottom = Some(
+            row.iter()
+                .map(|w| w.rect.y1)
+                .fold(f64::NEG_INFINITY, f64::max),
+        );
+
+        let mut line = String::new();
+        let mut width = 0;
+        for word in row {
+            let column = ((word.rect.x0 - left) / char_width).round().max(0.0) as usize;
+            // the words stay separated even when their columns collide
+            let spaces = if width == 0 {
+                column
+            } else {
+                column.saturating_sub(width).max(1)
+            };
+            line.extend(std::iter::repeat_n(' ', spaces));
+            line.push_str(word.text);
+            width += spaces + word.text.chars().count();
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two columns of two lines, recognized as rows across the columns
+    fn two_columns() -> OcrResult {
+        let word = |text, x, y| (text, Rect::new(x, y, x + 60.0, y + 20.0));
+        OcrResult::from_boxes(&[
+            &[word("left1", 0.0, 0.0), word("right1", 200.0, 0.0)],
+            &[word("left2", 0.0, 30.0), word("right2", 200.0, 30.0)],
+        ])
+    }
+
+    #[test]
+    fn lines_keep_engine_order() {
+        let text = text(&two_columns(), TextMode::Lines);
+        assert_eq!(text, "left1 right1\nleft2 right2");
+    }
+
+    #[test]
+    fn columns() {
+        let text = text(&two_columns(), TextMode::Columns);
+        assert_eq!(text, "left1\nleft2\nright1\nright2");
+    }
+}
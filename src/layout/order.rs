@@ -0,0 +1,79 @@
+use super::{group_rows, WordBox};
+use crate::ocr::{Line, OcrResult};
+
+use vello::kurbo::Rect;
+
+/// A column or a block of text separated from the others by white space
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+    pub rect: Rect,
+    // the words of the block, in rows
+    pub result: OcrResult,
+}
+
+/// Split the words in blocks by cutting along the widest white space
+/// (XY-cut), the blocks are in reading order, column by column
+pub fn blocks(result: &OcrResult) -> Vec<Block> {
+    let words: Vec<WordBox> = result.words().map(WordBox::from).collect();
+    let mut heights: Vec<_> = words.iter().map(|w| w.rect.height()).collect();
+    heights.sort_by(f64::total_cmp);
+    let Some(&line_height) = heights.get(heights.len() / 2) else {
+        return vec![];
+    };
+
+    let mut leaves = vec![];
+    cut(words, line_height, &mut leaves);
+    leaves
+        .into_iter()
+        .map(|words| {
+            let rect = words.iter().map(|w| w.rect).reduce(|a, b| a.union(b));
+            let lines = group_rows(words)
+                .into_iter()
+                .map(|row| Line {
+                    words: row.iter().map(|w| w.word.clone()).collect(),
+                })
+                .collect();
+            Block {
+                rect: rect.unwrap_or_default(),
+                result: OcrResult {
+                    lines,
+                    origin: result.origin,
+                },
+            }
+        })
+        .collect()
+}
+
+fn cut<'a>(words: Vec<WordBox<'a>>, line_height: f64, leaves: &mut Vec<Vec<WordBox<'a>>>) {
+    // the gutters between columns are wider than the spaces between words,
+    // and the gaps between blocks taller than the spaces between lines
+    let column_gap = widest_gap(&words, |r| (r.x0, r.x1)).filter(|g| g.1 > 1.5 * line_height);
+    let block_gap = widest_gap(&words, |r| (r.y0, r.y1)).filter(|g| g.1 > 0.8 * line_height);
+
+    let (first, second): (Vec<_>, Vec<_>) = match (column_gap, block_gap) {
+        (Some((x, _)), _) => words.into_iter().partition(|w| w.rect.x1 <= x),
+        (None, Some((y, _))) => words.into_iter().partition(|w| w.rect.y1 <= y),
+        (None, None) => {
+            leaves.push(words);
+            return;
+        }
+    };
+    cut(first, line_height, leaves);
+    cut(second, line_height, leaves);
+}
+
+// The start and width of the widest gap between the projections of the
+// words on an axis
+fn widest_gap(words: &[WordBox], axis: impl Fn(&Rect) -> (f64, f64)) -> Option<(f64, f64)> {
+    let mut ranges: Vec<_> = words.iter().map(|w| axis(&w.rect)).collect();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut end = ranges.first()?.1;
+    let mut widest: Option<(f64, f64)> = None;
+    for (start, stop) in ranges {
+        if start > end && widest.is_none_or(|(_, width)| start - end > width) {
+            widest = Some((end, start - end));
+        }
+        end = end.max(stop);
+    }
+    widest
+}
@@ -0,0 +1,66 @@
+use super::rows;
+use crate::ocr::OcrResult;
+
+use vello::kurbo::Rect;
+
+/// Join the lines of prose into paragraphs, one per line of text, separated
+/// by an empty line
+pub fn paragraphs_text(result: &OcrResult) -> String {
+    let lines: Vec<(Rect, String)> = rows(result)
+        .iter()
+        .map(|row| {
+            let bound = row
+                .iter()
+                .map(|w| w.rect)
+                .reduce(|a, b| a.union(b))
+                .unwrap();
+            let words: Vec<_> = row.iter().map(|w| w.text).collect();
+            (bound, words.join(" "))
+        })
+        .collect();
+    let Some(left) = lines.iter().map(|(r, _)| r.x0).reduce(f64::min) else {
+        return String::new();
+    };
+    let right = lines.iter().map(|(r, _)| r.x1).fold(left, f64::max);
+    let mut heights: Vec<_> = lines.iter().map(|(r, _)| r.height()).collect();
+    heights.sort_by(f64::total_cmp);
+    let line_height = heights[heights.len() / 2];
+
+    let mut paragraphs: Vec<String> = vec![];
+    let mut previous: Option<&(Rect, String)> = None;
+    for line in &lines {
+        let (rect, text) = line;
+        let starts_paragraph = previous.is_none_or(|(prev, prev_text)| {
+            // a wider gap than between the lines of a paragraph
+            let gap = rect.y0 - prev.y1 > 0.8 * line_height;
+            // an indented first line
+            let indent = rect.x0 - left > line_height && prev.x0 - left < 0.5 * line_height;
+            // the last line of a paragraph stops before the margin
+            let short = right - prev.x1 > 4.0 * line_height
+                && prev_text.ends_with(['.', '!', '?', ':'])
+                && text.starts_with(char::is_uppercase);
+            gap || indent || short
+        });
+        previous = Some(line);
+
+        let paragraph = match paragraphs.last_mut() {
+            Some(paragraph) if !starts_paragraph => paragraph,
+            _ => {
+                paragraphs.push(text.clone());
+                continue;
+            }
+        };
+        // a word hyphenated at the end of the line continues on the next one
+        let hyphenated = paragraph
+            .strip_suffix('-')
+            .is_some_and(|rest| rest.ends_with(char::is_alphabetic))
+            && text.starts_with(char::is_lowercase);
+        if hyphenated {
+            paragraph.pop();
+        } else {
+            paragraph.push(' ');
+        }
+        paragraph.push_str(text);
+    }
+    paragraphs.join("\n\n")
+}
@@ -0,0 +1,296 @@
+use super::{overlap, rows, WordBox};
+use crate::ocr::OcrResult;
+
+use clap::ValueEnum;
+use vello::kurbo::Rect;
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    #[default]
+    Tsv,
+    Csv,
+    Markdown,
+    Html,
+}
+
+/// The cells of the words laid out in rows and columns
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(result: &OcrResult) -> Self {
+        // the words of a row separated by a wide gap are in different cells
+        let cells: Vec<Vec<(Rect, String)>> = rows(result)
+            .iter()
+            .map(|row| {
+                let height = row.iter().map(|w| w.rect.height()).fold(0.0, f64::max);
+                let mut cells: Vec<(Rect, Vec<&WordBox>)> = vec![];
+                for word in row {
+                    match cells.last_mut() {
+                        Some((bound, words)) if word.rect.x0 - bound.x1 < 0.8 * height => {
+                            *bound = bound.union(word.rect);
+                            words.push(word);
+                        }
+                        _ => cells.push((word.rect, vec![word])),
+                    }
+                }
+                cells
+                    .into_iter()
+                    .map(|(bound, words)| {
+                        let words: Vec<_> = words.iter().map(|w| w.text).collect();
+                        (bound, words.join(" "))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // the columns are the horizontal ranges covered by overlapping cells,
+        // leaving out the cells spanning several cells of another row, like a
+        // title, so they do not merge the columns below them
+        let spans = |rect: &Rect, row: &Vec<(Rect, String)>| {
+            let covered = row
+                .iter()
+                .filter(|(other, _)| overlap(rect.x0..rect.x1, other.x0..other.x1) > 0.0);
+            covered.count() > 1
+        };
+        let mut ranges: Vec<_> = cells
+            .iter()
+            .flatten()
+            .filter(|(rect, _)| !cells.iter().any(|row| spans(rect, row)))
+            .map(|(r, _)| r.x0..r.x1)
+            .collect();
+        ranges.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let mut columns: Vec<std::ops::Range<f64>> = vec![];
+        for range in ranges {
+            match columns.last_mut() {
+                Some(column) if overlap(column.clone(), range.clone()) > 0.0 => {
+                    column.end = column.end.max(range.end);
+                }
+                _ => columns.push(range),
+            }
+        }
+
+        let rows = cells
+            .into_iter()
+            .map(|row| {
+                let mut table_row = vec![String::new(); columns.len()];
+                for (rect, text) in row {
+                    // a spanning cell goes in the first column it covers
+                    let column = columns
+                        .iter()
+                        .position(|c| overlap(c.clone(), rect.x0..rect.x1) > 0.0)
+                        .unwrap_or(0);
+                    let cell = &mut table_row[column];
+                    if !cell.is_empty() {
+                        cell.push(' ');
+                    }
+                    cell.push_str(&text);
+                }
+                table_row
+            })
+            .collect();
+        Self { rows }
+    }
+
+    pub fn format(&self, format: TableFormat) -> String {
+        let mut out = String::new();
+        match format {
+            TableFormat::Tsv => {
+                for row in &self.rows {
+                    let cells: Vec<_> = row.iter().map(|c| c.replace('\t', " ")).collect();
+                    out += &(cells.join("\t") + "\n");
+                }
+            }
+            TableFormat::Csv => {
+                for row in &self.rows {
+                    let cells: Vec<_> = row.iter().map(|c| csv_field(c)).collect();
+                    out += &(cells.join(",") + "\n");
+                }
+            }
+            TableFormat::Markdown => {
+                for (i, row) in self.rows.iter().enumerate() {
+                    let cells: Vec<_> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+                    out += &format!("| {} |\n", cells.join(" | "));
+                    // the first row is the header
+                    if i == 0 {
+                        out += &format!("|{}\n", " --- |".repeat(row.len()));
+                    }
+                }
+            }
+            TableFormat::Html => {
+                out += "<table>\n";
+                for row in &self.rows {
+                    out += "  <tr>";
+                    for cell in row {
+                        out += &format!("<td>{}</td>", html_escape(cell));
+                    }
+                    out += "</tr>\n";
+                }
+                out += "</table>\n";
+            }
+        }
+        out
+    }
+}
+
+/// Quote a CSV field when it contains a separator or a quote
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+    use crate::ocr;
+
+    fn table() -> Table {
+        let word = |text, x0, x1, y0| (text, Rect::new(x0, y0, x1, y0 + 20.0));
+        Table::new(&OcrResult::from_boxes(&[
+            &[
+                word("Quarterly", 0.0, 90.0, 0.0),
+                word("report", 100.0, 160.0, 0.0),
+            ],
+            &[
+                word("Name", 0.0, 40.0, 30.0),
+                word("Qty", 120.0, 150.0, 30.0),
+                word("Price", 200.0, 250.0, 30.0),
+            ],
+            &[
+                word("Apples,", 0.0, 70.0, 60.0),
+                word("red", 76.0, 106.0, 60.0),
+                word("3", 130.0, 140.0, 60.0),
+                word("$1.50", 200.0, 250.0, 60.0),
+            ],
+            &[
+                word("\"Kiwi\"", 0.0, 50.0, 90.0),
+                word("12", 125.0, 145.0, 90.0),
+                word("a|b<c>&", 200.0, 270.0, 90.0),
+            ],
+        ]))
+    }
+
+    #[test]
+    fn spanning_title() {
+        assert_eq!(
+            table().rows,
+            [
+                ["Quarterly report", "", ""],
+                ["Name", "Qty", "Price"],
+                ["Apples, red", "3", "$1.50"],
+                ["\"Kiwi\"", "12", "a|b<c>&"],
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv() {
+        assert_eq!(
+            table().format(TableFormat::Tsv),
+            "Quarterly report\t\t\n\
+             Name\tQty\tPrice\n\
+             Apples, red\t3\t$1.50\n\
+             \"Kiwi\"\t12\ta|b<c>&\n"
+        );
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            table().format(TableFormat::Csv),
+            "Quarterly report,,\n\
+             Name,Qty,Price\n\
+             \"Apples, red\",3,$1.50\n\
+             \"\"\"Kiwi\"\"\",12,a|b<c>&\n"
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            table().format(TableFormat::Markdown),
+            "| Quarterly report |  |  |\n\
+             | --- | --- | --- |\n\
+             | Name | Qty | Price |\n\
+             | Apples, red | 3 | $1.50 |\n\
+             | \"Kiwi\" | 12 | a\\|b<c>& |\n"
+        );
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            table().format(TableFormat::Html),
+            "<table>\n  \
+             <tr><td>Quarterly report</td><td></td><td></td></tr>\n  \
+             <tr><td>Name</td><td>Qty</td><td>Price</td></tr>\n  \
+             <tr><td>Apples, red</td><td>3</td><td>$1.50</td></tr>\n  \
+             <tr><td>\"Kiwi\"</td><td>12</td><td>a|b&lt;c&gt;&amp;</td></tr>\n\
+             </table>\n"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let table = Table::new(&OcrResult::default());
+        assert!(table.rows.is_empty());
+        assert_eq!(table.format(TableFormat::Html), "<table>\n</table>\n");
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn rendered_table() {
+        let cells = [
+            ("Name", "Qty", "Price"),
+            ("Apples", "3", "1.50"),
+            ("Kiwis", "12", "0.25"),
+        ];
+        let texts: Vec<_> = cells
+            .iter()
+            .enumerate()
+            .flat_map(|(row, (name, qty, price))| {
+                let y = row as u32 * 40;
+                [(0, y, *name), (200, y, *qty), (320, y, *price)]
+            })
+            .collect();
+        let image = fixtures::render_at(&texts, Style::default());
+        let result = ocr::recognize(image.as_raw(), image.dimensions()).unwrap();
+        let table = Table::new(&result);
+
+        assert_eq!(
+            table.format(TableFormat::Tsv),
+            "Name\tQty\tPrice\nApples\t3\t1.50\nKiwis\t12\t0.25\n"
+        );
+        assert_eq!(
+            table.format(TableFormat::Csv),
+            "Name,Qty,Price\nApples,3,1.50\nKiwis,12,0.25\n"
+        );
+        assert_eq!(
+            table.format(TableFormat::Markdown),
+            "| Name | Qty | Price |\n\
+             | --- | --- | --- |\n\
+             | Apples | 3 | 1.50 |\n\
+             | Kiwis | 12 | 0.25 |\n"
+        );
+        assert_eq!(
+            table.format(TableFormat::Html),
+            "<table>\n  \
+             <tr><td>Name</td><td>Qty</td><td>Price</td></tr>\n  \
+             <tr><td>Apples</td><td>3</td><td>1.50</td></tr>\n  \
+             <tr><td>Kiwis</td><td>12</td><td>0.25</td></tr>\n\
+             </table>\n"
+        );
+    }
+}
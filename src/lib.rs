@@ -0,0 +1,22 @@
+pub mod app;
+pub mod capture;
+pub mod cli;
+pub mod clipboard;
+pub mod dirs;
+#[cfg(test)]
+mod fixtures;
+pub mod hotkey;
+#[cfg(unix)]
+pub mod ipc;
+pub mod layout;
+pub mod locate;
+pub mod numbers;
+pub mod ocr;
+pub mod pointer;
+pub mod preprocess;
+pub mod scenes;
+pub mod state;
+pub mod subtitles;
+pub mod trigger;
+pub mod wait;
+pub mod watch;
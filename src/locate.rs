@@ -0,0 +1,350 @@
+use crate::capture::Capturer;
+use crate::cli::{ClickArgs, ClickTarget, LocateArgs};
+use crate::ocr::{self, OcrResult, Word};
+use crate::pointer;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Serialize;
+use vello::kurbo::{Point, Rect, Vec2};
+
+use std::io::Write;
+
+/// What is searched in the recognized lines
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Words compared loosely, ignoring case
+    Text(String),
+    /// A regular expression searched in the text of each line
+    Pattern(Regex),
+}
+
+/// Consecutive words of a line matching the query, in screen coordinates
+#[derive(Debug, Clone, Serialize)]
+pub struct Found {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    // how close the words are to the query, from 0 to 1
+    pub score: f64,
+    #[serde(skip)]
+    pub line: usize,
+}
+
+impl Found {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.x + self.width, self.y + self.height)
+    }
+}
+
+impl Query {
+    pub fn new(query: &str, regex: bool) -> Result<Self> {
+        if regex {
+            return Ok(Self::Pattern(Regex::new(query)?));
+        }
+        if query.trim().is_empty() {
+            bail!("the searched text is empty");
+        }
+        Ok(Self::Text(query.to_string()))
+    }
+}
+
+/// Find the words of the result matching the query with at least the given
+/// score, the best matches first
+pub fn find(result: &OcrResult, query: &Query, threshold: f64) -> Vec<Found> {
+    let mut found = vec![];
+    for (index, line) in result.lines.iter().enumerate() {
+        let words = &line.words;
+        // the ranges of words matching the query with their score
+        let mut ranges = vec![];
+        match query {
+            Query::Text(text) => {
+                let text = text.to_lowercase();
+                let n = text.split_whitespace().count();
+                // the recognition may split or merge the words of the query
+                for size in n.saturating_sub(1).max(1)..=n + 1 {
+                    for start in 0..=words.len().saturating_sub(size) {
+                        let end = (start + size).min(words.len());
+                        let phrase = join(&words[start..end]).to_lowercase();
+                        let score = similarity(&phrase, &text);
+                        if score >= threshold {
+                            ranges.push((start..end, score));
+                        }
+                    }
+                }
+                ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+                // keep the best of the overlapping phrases
+                let mut kept: Vec<(std::ops::Range<usize>, f64)> = vec![];
+                for (range, score) in ranges {
+                    if !kept
+                        .iter()
+                        .any(|(k, _)| k.start < range.end && range.start < k.end)
+                    {
+                        kept.push((range, score));
+                    }
+                }
+                kept.sort_by_key(|(range, _)| range.start);
+                ranges = kept;
+            }
+            Query::Pattern(pattern) => {
+                let text = join(words);
+                // the offset of each word in the text of the line
+                let mut offsets = vec![];
+                let mut offset = 0;
+                for word in words {
+                    offsets.push(offset..offset + word.text.len());
+                    offset += word.text.len() + 1;
+                }
+                for m in pattern.find_iter(&text).filter(|m| !m.is_empty()) {
+                    let start = offsets.iter().position(|o| o.end > m.start());
+                    let end = offsets.iter().rposition(|o| o.start < m.end());
+                    if let (Some(start), Some(end)) = (start, end) {
+                        ranges.push((start..end + 1, 1.0));
+                    }
+                }
+            }
+        }
+
+        for (range, score) in ranges {
+            // a line without words matches an empty range with a zero threshold
+            let Some(rect) = words[range.clone()]
+                .iter()
+                .map(|word| result.screen_rect(word))
+                .reduce(|a, b| a.union(b))
+            else {
+                continue;
+            };
+            found.push(Found {
+                text: join(&words[range]),
+                x: rect.x0,
+                y: rect.y0,
+                width: rect.width(),
+                height: rect.height(),
+                score,
+                line: index,
+            });
+        }
+    }
+    found.sort_by(|a, b| b.score.total_cmp(&a.score));
+    found
+}
+
+fn join(words: &[Word]) -> String {
+    let words: Vec<_> = words.iter().map(|w| w.text.as_str()).collect();
+    words.join(" ")
+}
+
+// One minus the edit distance of the texts relative to the longest one
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / a.len().max(b.len()) as f64
+}
+
+/// Recognize the searched region, the image file or the whole screen
+pub fn recognize(args: &LocateArgs) -> Result<OcrResult> {
+    if let Some(ref path) = args.file {
+        return ocr::recognize_file(path);
+    }
+    let rect = match args.region {
+        Some(rect) => rect,
+        None => Capturer::new()?.bounds(),
+    };
+    ocr::recognize_region(rect)
+}
+
+/// Print the boxes of the words matching the query, fails when there are none
+pub fn locate(args: &LocateArgs, json: bool) -> Result<()> {
+    let query = Query::new(&args.query, args.regex)?;
+    let found = find(&recognize(args)?, &query, args.threshold);
+    if found.is_empty() {
+        bail!("`{}` was not found", args.query);
+    }
+    if json {
+        println!("{}", serde_json::to_string(&found)?);
+        return Ok(());
+    }
+    for f in found {
+        println!(
+            "{} {} {} {} {:.2} {}",
+            f.x, f.y, f.width, f.height, f.score, f.text
+        );
+    }
+    Ok(())
+}
+
+/// Click the centre of the best match, or of its whole line
+pub fn click(args: &LocateArgs, click: &ClickArgs) -> Result<()> {
+    let result = recognize(args)?;
+    click_found(&result, args, click, &mut std::io::stdout())
+}
+
+// Click the best match of the result, or print the click for a dry run
+fn click_found(
+    result: &OcrResult,
+    args: &LocateArgs,
+    click: &ClickArgs,
+    out: &mut impl Write,
+) -> Result<()> {
+    let query = Query::new(&args.query, args.regex)?;
+    let Some(found) = find(result, &query, args.threshold).into_iter().next() else {
+        bail!("`{}` was not found", args.query);
+    };
+    let point = click_point(result, &found, click);
+    let count = if click.double { 2 } else { 1 };
+    if click.dry_run {
+        writeln!(
+            out,
+            "click {count}x at {} {} on {}",
+            point.x, point.y, found.text
+        )?;
+        return Ok(());
+    }
+    pointer::click(point, count)
+}
+
+// The centre of the match or of its line, moved by the offsets
+fn click_point(result: &OcrResult, found: &Found, click: &ClickArgs) -> Point {
+    let rect = match click.target {
+        ClickTarget::Match => found.rect(),
+        ClickTarget::Line => result.lines[found.line]
+            .words
+            .iter()
+            .map(|word| result.screen_rect(word))
+            .fold(found.rect(), |a, b| a.union(b)),
+    };
+    rect.center() + Vec2::new(click.offset_x, click.offset_y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A line of words 50 pixels wide, 10 pixels apart
+    fn line(words: &[&'static str]) -> Vec<(&'static str, Rect)> {
+        let boxes = words.iter().enumerate().map(|(index, &text)| {
+            let x = index as f64 * 60.0;
+            (text, Rect::new(x, 20.0, x + 50.0, 40.0))
+        });
+        boxes.collect()
+    }
+
+    fn texts(found: &[Found]) -> Vec<&str> {
+        found.iter().map(|found| found.text.as_str()).collect()
+    }
+
+    #[test]
+    fn similar_texts() {
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("save", "save"), 1.0);
+        assert_eq!(similarity("", "save"), 0.0);
+        assert_eq!(similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        assert_eq!(similarity("save", "saves"), 0.8);
+    }
+
+    #[test]
+    fn split_and_merged_words() {
+        let result = OcrResult::from_boxes(&[
+            &line(&["Please", "Sub", "mit", "the", "form"]),
+            &line(&["Save", "asdraft"]),
+        ]);
+        let query = Query::new("submit", false).unwrap();
+        assert_eq!(texts(&find(&result, &query, 0.8)), ["Sub mit"]);
+        let query = Query::new("as draft", false).unwrap();
+        assert_eq!(texts(&find(&result, &query, 0.8)), ["asdraft"]);
+        let query = Query::new("cancel", false).unwrap();
+        assert!(find(&result, &query, 0.8).is_empty());
+    }
+
+    #[test]
+    fn pattern_offsets() {
+        let result = OcrResult::from_boxes(&[&line(&["Total:", "1,234", "EUR", "due"])]);
+        let query = Query::new(r"\d+,\d+ EUR", true).unwrap();
+        let found = find(&result, &query, 0.8);
+        assert_eq!(texts(&found), ["1,234 EUR"]);
+        assert_eq!((found[0].x, found[0].width), (60.0, 110.0));
+        // a match inside a word selects the whole word
+        let query = Query::new("ta", true).unwrap();
+        assert_eq!(texts(&find(&result, &query, 0.8)), ["Total:"]);
+    }
+
+    #[test]
+    fn screen_coordinates() {
+        let mut result = OcrResult::from_boxes(&[&line(&["Open", "file"])]);
+        result.origin = Point::new(100.0, 50.0);
+        let query = Query::new("file", false).unwrap();
+        let found = find(&result, &query, 0.8);
+        assert_eq!(found[0].rect(), Rect::new(160.0, 70.0, 210.0, 90.0));
+        assert_eq!(found[0].line, 0);
+    }
+
+    #[test]
+    fn empty_lines() {
+        let result = OcrResult::from_boxes(&[&[], &line(&["Open"])]);
+        let query = Query::new("open", false).unwrap();
+        assert_eq!(texts(&find(&result, &query, 0.0)), ["Open"]);
+    }
+
+    fn click_args(target: ClickTarget, offset: (f64, f64)) -> ClickArgs {
+        ClickArgs {
+            target,
+            offset_x: offset.0,
+            offset_y: offset.1,
+            double: false,
+            dry_run: true,
+        }
+    }
+
+    fn dry_run(query: &str, click: &ClickArgs) -> String {
+        let mut result = OcrResult::from_boxes(&[
+            &line(&["Name"]),
+            &[
+                ("Save", Rect::new(0.0, 60.0, 50.0, 80.0)),
+                ("as", Rect::new(60.0, 60.0, 80.0, 80.0)),
+                ("draft", Rect::new(90.0, 60.0, 200.0, 80.0)),
+            ],
+        ]);
+        result.origin = Point::new(100.0, 50.0);
+        let args = LocateArgs {
+            query: query.to_string(),
+            regex: false,
+            region: None,
+            file: None,
+            threshold: 0.8,
+        };
+        let mut out = vec![];
+        click_found(&result, &args, click, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn click_points() {
+        let click = click_args(ClickTarget::Match, (0.0, 0.0));
+        assert_eq!(dry_run("as", &click), "click 1x at 170 120 on as\n");
+        // the centre of the whole line
+        let click = click_args(ClickTarget::Line, (0.0, 0.0));
+        assert_eq!(dry_run("as", &click), "click 1x at 200 120 on as\n");
+        let click = click_args(ClickTarget::Match, (-20.0, 5.5));
+        assert_eq!(dry_run("as", &click), "click 1x at 150 125.5 on as\n");
+        let click = ClickArgs {
+            double: true,
+            ..click_args(ClickTarget::Line, (10.0, 0.0))
+        };
+        assert_eq!(dry_run("name", &click), "click 2x at 135 80 on Name\n");
+    }
+}
@@ -1,28 +1,187 @@
-pub mod app;
-pub mod capture;
-pub mod scenes;
-pub mod state;
-
 use anyhow::Result;
+use clap::Parser;
+use screen_ocr::cli::{self, Command, OutputArgs, Settings};
+#[cfg(unix)]
+use screen_ocr::ipc;
+use screen_ocr::layout::{self, table::Table};
+use screen_ocr::ocr::{self, OcrResult};
+use screen_ocr::subtitles::SubtitleFormat;
+use screen_ocr::{app, clipboard, hotkey, locate, numbers, state, trigger, wait, watch};
+use vello::kurbo::Rect;
 use vello::util::RenderContext;
 use winit::event_loop::EventLoop;
 
 fn main() -> Result<()> {
+    let cli = cli::Cli::parse();
+    // before any other thread exists
+    ocr::set_threads(cli.settings.threads);
+    ocr::set_options(cli.settings.ocr_options());
+
+    match cli.command {
+        Some(Command::ClipboardServe {
+            selection,
+            clear_after,
+        }) => return clipboard::serve_stdin(&selection, clear_after.map(Into::into)),
+        Some(Command::UndoCopy) => return clipboard::restore_previous(),
+        Some(Command::Client { request }) => return client(&request.join(" ")),
+        Some(Command::Watch {
+            watch: args,
+            output,
+            subtitles,
+            subtitle_format,
+        }) => {
+            let subtitles = subtitles.as_deref().map(|path| {
+                let format = subtitle_format.unwrap_or_else(|| SubtitleFormat::from_path(path));
+                (path, format)
+            });
+            return watch::watch(&args, output.as_deref(), subtitles);
+        }
+        Some(Command::Trigger { watch: args, rule }) => return trigger::trigger(&args, &rule),
+        Some(Command::Locate { locate: args, json }) => return locate::locate(&args, json),
+        Some(Command::Click {
+            locate: args,
+            click,
+        }) => return locate::click(&args, &click),
+        Some(Command::WaitFor {
+            text,
+            regex,
+            timeout,
+            watch: args,
+        }) => return wait::wait_for(&args, &text, regex, timeout.into()),
+        Some(Command::LogValues {
+            watch: args,
+            csv,
+            locale,
+        }) => return numbers::log_values(&args, &csv, locale),
+        _ => {}
+    }
+
+    // a single instance shows the overlay, the others forward their request to it
+    #[cfg(unix)]
+    let Some(instance) = ipc::lock_instance()?
+    else {
+        return forward(&cli.settings, &cli.command);
+    };
+
+    match cli.command {
+        Some(Command::Region {
+            x,
+            y,
+            width,
+            height,
+            ref output,
+        }) => {
+            #[cfg(unix)]
+            drop(instance);
+            let result = ocr::recognize_region(Rect::new(x, y, x + width, y + height))?;
+            print_result(&result, &cli.settings, output);
+            return Ok(());
+        }
+        Some(Command::File {
+            ref path,
+            ref output,
+        }) => {
+            #[cfg(unix)]
+            drop(instance);
+            print_result(&ocr::recognize_file(path)?, &cli.settings, output);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let event_loop = EventLoop::<app::UserEvent>::with_user_event().build()?;
+    #[cfg(unix)]
+    ipc::serve(instance, event_loop.create_proxy())?;
+    let resident = matches!(cli.command, Some(Command::Daemon { .. }));
+    if let Some(Command::Daemon { hotkey }) = cli.command {
+        hotkey::listen(hotkey, event_loop.create_proxy())?;
+        // keep the models loaded for the next captures
+        std::thread::spawn(|| {
+            if let Err(err) = ocr::engine() {
+                eprintln!("warning: cannot load the OCR models: {err:#}");
+            }
+        });
+    }
+
     let mut app = app::App {
         context: RenderContext::new(),
         active: 0,
         renderers: vec![],
         surfaces: vec![],
         windows: vec![],
-        state: Default::default(),
+        state: state::AppState {
+            settings: cli.settings,
+            ..Default::default()
+        },
         view: Default::default(),
         callbacks: vec![],
+        resident,
     };
 
-    // Create and run a winit event loop
-    let event_loop = EventLoop::new()?;
+    // Run the winit event loop
     event_loop
         .run_app(&mut app)
         .expect("Couldn't run event loop");
     Ok(())
 }
+
+fn print_result(result: &OcrResult, settings: &Settings, output: &OutputArgs) {
+    if output.table {
+        print!("{}", Table::new(result).format(settings.table_format));
+    } else {
+        println!("{}", layout::text(result, settings.text_mode));
+    }
+}
+
+#[cfg(unix)]
+fn client(request: &str) -> Result<()> {
+    let request = match request.parse()? {
+        // the running instance may have another working directory
+        ipc::Request::File(path) => ipc::Request::File(path.canonicalize()?),
+        request => request,
+    };
+    let response = ipc::send(&request, std::time::Duration::ZERO)?;
+    println!("{}", serde_json::to_string(&response)?);
+    if !response.ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn client(_request: &str) -> Result<()> {
+    anyhow::bail!("the control socket is only available on unix")
+}
+
+// Send the request of the command line to the running instance
+#[cfg(unix)]
+fn forward(settings: &Settings, command: &Option<Command>) -> Result<()> {
+    let default_output = OutputArgs::default();
+    let (request, output) = match command {
+        None => (ipc::Request::Select, &default_output),
+        Some(Command::Region {
+            x,
+            y,
+            width,
+            height,
+            output,
+        }) => (
+            ipc::Request::Region(Rect::new(*x, *y, x + width, y + height)),
+            output,
+        ),
+        // the running instance may have another working directory
+        Some(Command::File { path, output }) => (ipc::Request::File(path.canonicalize()?), output),
+        Some(_) => anyhow::bail!("another instance is already running"),
+    };
+    let response = ipc::send(&request, std::time::Duration::from_secs(2))?;
+    if let Some(err) = response.error {
+        anyhow::bail!(err);
+    }
+    // the other modes need the word boxes
+    if output.table || settings.text_mode != layout::TextMode::Lines {
+        print_result(&OcrResult::from(&response), settings, output);
+    } else {
+        println!("{}", response.text);
+    }
+    Ok(())
+}
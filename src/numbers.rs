@@ -0,0 +1,237 @@
+use crate::cli::WatchArgs;
+use crate::layout::table::csv_field;
+use crate::watch::Watcher;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use regex::Regex;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// The separators used to write the numbers
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum NumberLocale {
+    /// 1,234.5
+    #[default]
+    En,
+    /// 1.234,5
+    De,
+    /// 1 234,5
+    Fr,
+    /// 1'234.5
+    Ch,
+}
+
+impl NumberLocale {
+    fn decimal(self) -> char {
+        match self {
+            Self::En | Self::Ch => '.',
+            Self::De | Self::Fr => ',',
+        }
+    }
+
+    fn thousands(self) -> &'static str {
+        match self {
+            Self::En => ",",
+            Self::De => ".",
+            // regular, no-break and narrow no-break spaces
+            Self::Fr => " \u{a0}\u{202f}",
+            Self::Ch => "'\u{2019}",
+        }
+    }
+}
+
+/// A number found in the text with the unit written after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    pub number: f64,
+    pub unit: String,
+}
+
+/// Finds the numbers of a text written with the separators of a locale
+pub struct NumberParser {
+    pattern: Regex,
+    locale: NumberLocale,
+}
+
+impl NumberParser {
+    pub fn new(locale: NumberLocale) -> Self {
+        let decimal = regex::escape(&locale.decimal().to_string());
+        let thousands = regex::escape(locale.thousands());
+        let pattern = format!(
+            r"(?P<sign>[-+\u{{2212}}])?(?P<int>\d{{1,3}}(?:[{thousands}]\d{{3}})+|\d+)(?:{decimal}(?P<frac>\d+))?(?:\s?(?P<unit>[%‰°]|[\p{{L}}µ/]+))?"
+        );
+        Self {
+            pattern: Regex::new(&pattern).unwrap(),
+            locale,
+        }
+    }
+
+    /// Parse the numbers of the text, the errors describe the numbers that
+    /// could not be read
+    pub fn parse(&self, text: &str) -> Result<Vec<Value>, String> {
+        let mut values = vec![];
+        for captures in self.pattern.captures_iter(text) {
+            let int: String = captures["int"]
+                .chars()
+                .filter(|c| !self.locale.thousands().contains(*c))
+                .collect();
+            let frac = captures.name("frac").map_or("0", |m| m.as_str());
+            let number = format!("{int}.{frac}")
+                .parse::<f64>()
+                .map_err(|err| format!("cannot read `{}`: {err}", &captures[0]))?;
+            let negative = captures
+                .name("sign")
+                .is_some_and(|sign| sign.as_str() != "+");
+            values.push(Value {
+                number: if negative { -number } else { number },
+                unit: captures
+                    .name("unit")
+                    .map_or(String::new(), |m| m.as_str().to_string()),
+            });
+        }
+        if values.is_empty() {
+            return Err(format!("no number in `{}`", text.replace('\n', " ")));
+        }
+        Ok(values)
+    }
+}
+
+/// Append a row per number of the region to a CSV file at each capture,
+/// the captures whose text has no number are reported and skipped
+pub fn log_values(args: &WatchArgs, csv: &Path, locale: NumberLocale) -> Result<()> {
+    log_values_to(args, csv, locale, &mut std::io::stderr())
+}
+
+fn log_values_to(
+    args: &WatchArgs,
+    csv: &Path,
+    locale: NumberLocale,
+    warnings: &mut impl Write,
+) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(csv)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "timestamp,elapsed,index,value,unit")?;
+    }
+
+    let parser = NumberParser::new(locale);
+    let mut watcher = Watcher::new(args)?;
+    // the values of the last recognized capture, kept while the pixels don't change
+    let mut values = None;
+    while let Some(frame) = watcher.next_frame()? {
+        if let Some(result) = frame.result {
+            values = match parser.parse(&result.text()) {
+                Ok(parsed) => Some(parsed),
+                Err(err) => {
+                    writeln!(warnings, "warning: {err}")?;
+                    None
+                }
+            };
+        }
+        let Some(ref values) = values else {
+            continue;
+        };
+        let timestamp = humantime::format_rfc3339_millis(SystemTime::now());
+        let elapsed = frame.time.as_secs_f64();
+        for (index, value) in values.iter().enumerate() {
+            writeln!(
+                file,
+                "{timestamp},{elapsed},{index},{},{}",
+                value.number,
+                csv_field(&value.unit)
+            )?;
+        }
+        file.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::time::Duration;
+
+    fn numbers(locale: NumberLocale, text: &str) -> Vec<f64> {
+        let values = NumberParser::new(locale).parse(text).unwrap();
+        values.iter().map(|value| value.number).collect()
+    }
+
+    #[test]
+    fn separators() {
+        use NumberLocale::*;
+        assert_eq!(numbers(En, "1,234.5"), [1234.5]);
+        assert_eq!(numbers(En, "1.234,5"), [1.234, 5.0]);
+        assert_eq!(numbers(De, "1.234,5"), [1234.5]);
+        assert_eq!(numbers(De, "1,234.5"), [1.234, 5.0]);
+        assert_eq!(numbers(Fr, "1 234,5"), [1234.5]);
+        assert_eq!(numbers(Fr, "1\u{202f}234,5"), [1234.5]);
+        assert_eq!(numbers(Fr, "1,234.5"), [1.234, 5.0]);
+        assert_eq!(numbers(Fr, "1.234,5"), [1.0, 234.5]);
+        assert_eq!(numbers(Ch, "1'234.5"), [1234.5]);
+        assert_eq!(numbers(Ch, "1,234.5"), [1.0, 234.5]);
+        assert_eq!(numbers(Ch, "1.234,5"), [1.234, 5.0]);
+    }
+
+    #[test]
+    fn negatives() {
+        let locale = NumberLocale::En;
+        assert_eq!(numbers(locale, "-3.5"), [-3.5]);
+        assert_eq!(numbers(locale, "\u{2212}2 and +4"), [-2.0, 4.0]);
+    }
+
+    #[test]
+    fn units() {
+        let parser = NumberParser::new(NumberLocale::En);
+        let value = |number, unit: &str| Value {
+            number,
+            unit: unit.to_string(),
+        };
+        assert_eq!(parser.parse("12.5 ms"), Ok(vec![value(12.5, "ms")]));
+        assert_eq!(parser.parse("45%"), Ok(vec![value(45.0, "%")]));
+        assert_eq!(
+            parser.parse("CPU 45% at 3.2GHz"),
+            Ok(vec![value(45.0, "%"), value(3.2, "GHz")])
+        );
+    }
+
+    #[test]
+    fn no_number() {
+        let parser = NumberParser::new(NumberLocale::En);
+        assert_eq!(
+            parser.parse("no value\nyet"),
+            Err("no number in `no value yet`".to_string())
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn failures_logged() {
+        let frames = fixtures::frames("values", &[Some("12.5 ms"), Some("Loading"), Some("45%")]);
+        let args = WatchArgs {
+            region: None,
+            frames: Some(frames),
+            interval: Duration::from_secs(1).into(),
+            threshold: 0.01,
+        };
+        let csv = fixtures::temp_dir("values-csv").join("values.csv");
+        let mut warnings = vec![];
+        log_values_to(&args, &csv, NumberLocale::En, &mut warnings).unwrap();
+
+        let warnings = String::from_utf8(warnings).unwrap();
+        assert_eq!(warnings, "warning: no number in `Loading`\n");
+        // the capture without a number is skipped and the next ones logged
+        let rows: Vec<_> = std::fs::read_to_string(csv)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(',').skip(1).collect::<Vec<_>>().join(","))
+            .collect();
+        assert_eq!(
+            rows,
+            ["elapsed,index,value,unit", "0,0,12.5,ms", "2,0,45,%"]
+        );
+    }
+}
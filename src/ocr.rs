@@ -0,0 +1,817 @@
+use crate::capture;
+use crate::preprocess::{self, Invert, Rotation, Step};
+use crate::scenes::RotatedRect;
+
+use anyhow::{anyhow, Context, Result};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use ocrs::{ImageSource, OcrEngine, OcrEngineParams, OcrInput, TextItem, TextLine};
+use rayon::prelude::*;
+use rten::Model;
+use rten_imageproc::PointF;
+use vello::kurbo::{Affine, Point, Rect};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// The engine is loaded once and shared by every extraction
+static ENGINE: Mutex<Option<Arc<OcrEngine>>> = Mutex::new(None);
+
+// The result of the last extraction, whatever started it
+static LAST_RESULT: Mutex<Option<OcrResult>> = Mutex::new(None);
+
+// The height the small text is upscaled to, up to a maximum scale
+const UPSCALED_TEXT_HEIGHT: f64 = 32.0;
+const MAX_UPSCALE: f64 = 4.0;
+
+// The smallest skew of the words, in radians, that is straightened
+const MIN_SKEW: f64 = 0.5 * std::f64::consts::PI / 180.0;
+
+// The share of the characters in plausible words of a result read upright
+const PLAUSIBLE: f64 = 0.7;
+
+// The most lines the engine recognizes in one batch, the lines are read in
+// chunks of a batch per thread and given back after each chunk
+const ENGINE_BATCH: usize = 20;
+
+// The pixels shared by two neighbouring tiles, more than a line of text
+const TILE_OVERLAP: u32 = 96;
+
+// The options of the command line, used when none are given
+static OPTIONS: Mutex<Option<OcrOptions>> = Mutex::new(None);
+
+/// How the captures are prepared before the recognition
+#[derive(Debug, Clone, Default)]
+pub struct OcrOptions {
+    pub invert: Invert,
+    pub preprocess: Vec<Step>,
+    // the lines are upscaled when their median height in pixels is below this
+    pub upscale_below: f64,
+    pub deskew: bool,
+    pub rotation: Rotation,
+    // only the pixels close to this color are kept when set
+    pub text_color: Option<[u8; 3]>,
+    pub color_tolerance: f64,
+    // the margins without text are cropped, keeping this padding in pixels
+    pub trim: Option<u32>,
+    // the larger images are read in tiles of this size, 0 never tiles them
+    pub tile_size: u32,
+}
+
+/// The image given to the engine
+#[derive(Debug, Clone)]
+pub struct Prepared {
+    pub image: RgbaImage,
+    // maps the prepared image back to the captured one
+    pub transform: Affine,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Word {
+    pub text: String,
+    pub rect: RotatedRect,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    pub words: Vec<Word>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OcrResult {
+    pub lines: Vec<Line>,
+    // the top left corner of the recognized image on the screen
+    pub origin: Point,
+}
+
+impl Line {
+    pub fn text(&self) -> String {
+        let words: Vec<_> = self.words.iter().map(|w| w.text.as_str()).collect();
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+impl OcrResult {
+    /// A result with the words of each line at the given boxes
+    pub fn from_boxes(lines: &[&[(&str, Rect)]]) -> Self {
+        let lines = lines
+            .iter()
+            .map(|words| Line {
+                words: words
+                    .iter()
+                    .map(|&(text, rect)| Word {
+                        text: text.to_string(),
+                        rect: RotatedRect::from(rect),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            lines,
+            origin: Point::ZERO,
+        }
+    }
+}
+
+impl OcrResult {
+    pub fn text(&self) -> String {
+        let lines: Vec<_> = self.lines.iter().map(Line::text).collect();
+        lines.join("\n")
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &Word> {
+        self.lines.iter().flat_map(|line| line.words.iter())
+    }
+
+    /// The bounding box of a word in screen coordinates
+    pub fn screen_rect(&self, word: &Word) -> Rect {
+        Rect::from(&word.rect) + self.origin.to_vec2()
+    }
+}
+
+pub fn set_last_result(result: &OcrResult) {
+    *LAST_RESULT.lock().unwrap() = Some(result.clone());
+}
+
+pub fn last_result() -> Option<OcrResult> {
+    LAST_RESULT.lock().unwrap().clone()
+}
+
+/// Set the threads running the models, the engine reads their count once
+/// when it first runs so this is called before; 0 keeps the default of one
+/// per physical core or the RTEN_NUM_THREADS variable
+pub fn set_threads(threads: usize) {
+    if threads > 0 {
+        std::env::set_var("RTEN_NUM_THREADS", threads.to_string());
+    }
+}
+
+pub fn set_options(options: OcrOptions) {
+    *OPTIONS.lock().unwrap() = Some(options);
+}
+
+pub fn options() -> OcrOptions {
+    OPTIONS.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Get the shared engine, loading the models on the first call
+pub fn engine() -> Result<Arc<OcrEngine>> {
+    let mut engine = ENGINE.lock().unwrap();
+    if let Some(ref engine) = *engine {
+        return Ok(engine.clone());
+    }
+    let detection_model = Model::load_file("assets/text-detection.rten")?;
+    let recognition_model = Model::load_file("assets/text-recognition.rten")?;
+    let loaded = Arc::new(OcrEngine::new(OcrEngineParams {
+        detection_model: Some(detection_model),
+        recognition_model: Some(recognition_model),
+        ..Default::default()
+    })?);
+    *engine = Some(loaded.clone());
+    Ok(loaded)
+}
+
+/// Detect and recognize the words of rgba8 image bytes with the options of
+/// the command line
+pub fn recognize(data: &[u8], dimensions: (u32, u32)) -> Result<OcrResult> {
+    recognize_with(data, dimensions, &options())
+}
+
+pub fn recognize_with(
+    data: &[u8],
+    dimensions: (u32, u32),
+    options: &OcrOptions,
+) -> Result<OcrResult> {
+    recognize_prepared(&prepare(data, dimensions, options)?, options)
+}
+
+/// Run the preprocessing of the options on rgba8 image bytes
+pub fn prepare(data: &[u8], (width, height): (u32, u32), options: &OcrOptions) -> Result<Prepared> {
+    let image = RgbaImage::from_raw(width, height, data.to_vec())
+        .ok_or_else(|| anyhow!("the image is smaller than {width}x{height}"))?;
+    let (mut image, mut transform) = preprocess::turn(&image, options.rotation);
+    if let Some(color) = options.text_color {
+        preprocess::isolate_color(&mut image, color, options.color_tolerance);
+    }
+    preprocess::invert(&mut image, options.invert);
+    preprocess::apply(&mut image, &options.preprocess);
+    if let Some(padding) = options.trim {
+        if let Some([x0, y0, x1, y1]) = preprocess::content_bounds(&image, padding) {
+            image = image::imageops::crop_imm(&image, x0, y0, x1 - x0, y1 - y0).to_image();
+            transform *= Affine::translate((x0 as f64, y0 as f64));
+        }
+    }
+    Ok(Prepared { image, transform })
+}
+
+/// Recognize the words of a prepared image, their boxes are mapped back to
+/// the captured image
+pub fn recognize_prepared(prepared: &Prepared, options: &OcrOptions) -> Result<OcrResult> {
+    recognize_streamed(prepared, options, |_| {})
+}
+
+/// Recognize the words of a prepared image like `recognize_prepared`, and
+/// give each line in reading order as soon as it and the lines before it
+/// are read; the lines of the tiled or automatically turned reads come
+/// at the end, once their order is known
+pub fn recognize_streamed(
+    prepared: &Prepared,
+    options: &OcrOptions,
+    mut on_line: impl FnMut(&Line),
+) -> Result<OcrResult> {
+    let engine = engine()?;
+    let turned = options.rotation == Rotation::Auto;
+    let mut lines = read_tiled(
+        &engine,
+        &prepared.image,
+        prepared.transform,
+        options,
+        &mut |line| {
+            if !turned {
+                on_line(line)
+            }
+        },
+    )?;
+    // a poorly read text may be turned, the other orientations are tried
+    if turned {
+        for rotation in [Rotation::Down, Rotation::Right, Rotation::Left] {
+            if plausibility(&lines) >= PLAUSIBLE {
+                break;
+            }
+            let (image, back) = preprocess::turn(&prepared.image, rotation);
+            let transform = prepared.transform * back;
+            let turned = read_tiled(&engine, &image, transform, options, &mut |_| {})?;
+            if plausibility(&turned) > plausibility(&lines) {
+                lines = turned;
+            }
+        }
+        lines.iter().for_each(on_line);
+    }
+    Ok(OcrResult {
+        lines,
+        origin: Point::ZERO,
+    })
+}
+
+// Read the large images in overlapping tiles on the recognition threads,
+// each word is kept from the tile where it is the farthest from the edges
+// and the lines cut by the edges of the tiles are joined back
+fn read_tiled(
+    engine: &OcrEngine,
+    image: &RgbaImage,
+    transform: Affine,
+    options: &OcrOptions,
+    on_line: &mut dyn FnMut(&Line),
+) -> Result<Vec<Line>> {
+    let (width, height) = image.dimensions();
+    let size = options.tile_size;
+    if size <= TILE_OVERLAP || (width <= size && height <= size) {
+        return read(engine, image, transform, options, on_line);
+    }
+    // the start of the tiles along an axis, the last one reaches the end
+    let starts = |length: u32| {
+        let mut starts = vec![0];
+        while starts[starts.len() - 1] + size < length {
+            starts.push(starts[starts.len() - 1] + size - TILE_OVERLAP);
+        }
+        starts
+    };
+    let (xs, ys) = (starts(width), starts(height));
+    let tiles: Vec<_> = ys
+        .iter()
+        .flat_map(|&y| xs.iter().map(move |&x| (x, y)))
+        .collect();
+
+    let tile_lines = rten::thread_pool().run(|| {
+        tiles
+            .par_iter()
+            .map(|&(x, y)| {
+                let (w, h) = (size.min(width - x), size.min(height - y));
+                let tile = image::imageops::crop_imm(image, x, y, w, h).to_image();
+                let offset = Affine::translate((x as f64, y as f64));
+                read(engine, &tile, offset, options, &mut |_| {})
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let lines = join_tiles(&tiles, tile_lines, size, (width, height));
+    let mut lines = reading_order(engine, lines)?;
+    for line in &mut lines {
+        for word in &mut line.words {
+            word.rect = transform * word.rect;
+        }
+        on_line(line);
+    }
+    Ok(lines)
+}
+
+// Join the lines read in the tiles starting at the given positions
+fn join_tiles(
+    tiles: &[(u32, u32)],
+    tile_lines: Vec<Vec<Line>>,
+    size: u32,
+    (width, height): (u32, u32),
+) -> Vec<Line> {
+    // the words of the overlaps are read twice or cut by an edge, the copy
+    // farthest from the edges of its tile inside the image is kept
+    let margin = |(x, y): (u32, u32), rect: Rect| {
+        let (w, h) = (size.min(width - x), size.min(height - y));
+        let mut margin = f64::INFINITY;
+        if x > 0 {
+            margin = margin.min(rect.x0 - x as f64);
+        }
+        if y > 0 {
+            margin = margin.min(rect.y0 - y as f64);
+        }
+        if x + w < width {
+            margin = margin.min((x + w) as f64 - rect.x1);
+        }
+        if y + h < height {
+            margin = margin.min((y + h) as f64 - rect.y1);
+        }
+        margin
+    };
+    let mut edge_words = vec![];
+    for (tile, lines) in tile_lines.iter().enumerate() {
+        for (line, words) in lines.iter().enumerate() {
+            for (word, w) in words.words.iter().enumerate() {
+                let rect = Rect::from(&w.rect);
+                let margin = margin(tiles[tile], rect);
+                if margin < TILE_OVERLAP as f64 {
+                    edge_words.push(((tile, line, word), rect, margin));
+                }
+            }
+        }
+    }
+    let mut dropped = std::collections::HashSet::new();
+    for (i, &(id, rect, margin)) in edge_words.iter().enumerate() {
+        let better_copy = edge_words.iter().enumerate().any(|(j, &(other, r, m))| {
+            let common = rect.intersect(r).area();
+            other.0 != id.0
+                && common > 0.5 * rect.area().min(r.area())
+                && (m > margin || (m == margin && j < i))
+        });
+        if better_copy {
+            dropped.insert(id);
+        }
+    }
+
+    // a line crossing the edge of a tile is continued by a line of the
+    // neighbouring tile on the same row
+    let mut rows: Vec<(Vec<usize>, Rect, Line)> = vec![];
+    let mut tile_lines: Vec<_> = tile_lines
+        .into_iter()
+        .enumerate()
+        .flat_map(|(tile, lines)| {
+            lines
+                .into_iter()
+                .enumerate()
+                .map(move |(i, l)| (tile, i, l))
+        })
+        .filter_map(|(tile, line, mut words)| {
+            let mut index = 0;
+            words.words.retain(|_| {
+                index += 1;
+                !dropped.contains(&(tile, line, index - 1))
+            });
+            let bounds = words
+                .words
+                .iter()
+                .map(|word| Rect::from(&word.rect))
+                .reduce(|a, b| a.union(b))?;
+            Some((tile, bounds, words))
+        })
+        .collect();
+    tile_lines.sort_by(|a, b| a.1.x0.total_cmp(&b.1.x0));
+    for (tile, bounds, line) in tile_lines {
+        let continued = rows.iter_mut().find(|(tiles, row, _)| {
+            let height = row.height().min(bounds.height());
+            let vertical = row.y1.min(bounds.y1) - row.y0.max(bounds.y0);
+            !tiles.contains(&tile) && vertical > 0.5 * height && bounds.x0 - row.x1 < height
+        });
+        match continued {
+            Some((tiles, row, words)) => {
+                tiles.push(tile);
+                *row = row.union(bounds);
+                words.words.extend(line.words);
+                let left = |word: &Word| Rect::from(&word.rect).x0;
+                words.words.sort_by(|a, b| left(a).total_cmp(&left(b)));
+            }
+            None => rows.push((vec![tile], bounds, line)),
+        }
+    }
+    rows.into_iter().map(|(_, _, line)| line).collect()
+}
+
+// Sort the lines in reading order like the lines of a single read, the
+// engine orders them from the boxes of their words
+fn reading_order(engine: &OcrEngine, lines: Vec<Line>) -> Result<Vec<Line>> {
+    // the boxes are ordered without looking at the pixels
+    let input = engine.prepare_input(ImageSource::from_bytes(&[0; 4], (1, 1))?)?;
+    let key = |rect: &rten_imageproc::RotatedRect| {
+        let center = rect.center();
+        (center.x.to_bits(), center.y.to_bits())
+    };
+    let mut lines_of_words = HashMap::new();
+    let mut boxes = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        for word in &line.words {
+            let rect = Rect::from(&word.rect);
+            let center = rect.center();
+            let rect = rten_imageproc::RotatedRect::new(
+                PointF::from_yx(center.y as f32, center.x as f32),
+                rten_imageproc::Vec2::from_yx(-1.0, 0.0),
+                rect.width() as f32,
+                rect.height() as f32,
+            );
+            lines_of_words.insert(key(&rect), index);
+            boxes.push(rect);
+        }
+    }
+
+    // a line comes at the place of its first word in the order of the engine
+    let mut places = vec![usize::MAX; lines.len()];
+    let ordered = engine.find_text_lines(&input, &boxes);
+    for (place, rect) in ordered.iter().flatten().enumerate() {
+        if let Some(&index) = lines_of_words.get(&key(rect)) {
+            places[index] = places[index].min(place);
+        }
+    }
+    let mut lines: Vec<_> = lines.into_iter().zip(places).collect();
+    lines.sort_by_key(|(_, place)| *place);
+    Ok(lines.into_iter().map(|(line, _)| line).collect())
+}
+
+// Detect and recognize the lines of an image, straightened and upscaled
+// when needed, the transform maps the image to the captured one
+fn read(
+    engine: &OcrEngine,
+    image: &RgbaImage,
+    mut transform: Affine,
+    options: &OcrOptions,
+    on_line: &mut dyn FnMut(&Line),
+) -> Result<Vec<Line>> {
+    let (mut ocr_input, mut word_rects) = detect(engine, image)?;
+    let mut image = image;
+    let straightened;
+
+    if options.deskew {
+        let skew = skew(&word_rects);
+        if skew.abs() > MIN_SKEW {
+            let back;
+            (straightened, back) = preprocess::rotate(image, -skew);
+            image = &straightened;
+            (ocr_input, word_rects) = detect(engine, image)?;
+            transform *= back;
+        }
+    }
+
+    let line_rects = engine.find_text_lines(&ocr_input, &word_rects);
+    let pool = rten::thread_pool();
+    let chunk = ENGINE_BATCH * pool.run(rayon::current_num_threads);
+    let mut lines = vec![];
+    for rects in line_rects.chunks(chunk) {
+        let read = pool.run(|| recognize_lines(engine, &ocr_input, image, rects, options))?;
+        for mut line in read.into_iter().flatten() {
+            for word in &mut line.words {
+                word.rect = transform * word.rect;
+            }
+            on_line(&line);
+            lines.push(line);
+        }
+    }
+    Ok(lines)
+}
+
+// Recognize the lines of the image, in their order, on the engine threads;
+// small text is detected but misread, its lines are recognized from
+// upscaled crops, the engine batches the others by width
+fn recognize_lines(
+    engine: &OcrEngine,
+    ocr_input: &OcrInput,
+    image: &RgbaImage,
+    line_rects: &[Vec<rten_imageproc::RotatedRect>],
+    options: &OcrOptions,
+) -> Result<Vec<Option<Line>>> {
+    let (small, normal): (Vec<_>, Vec<_>) = line_rects
+        .iter()
+        .enumerate()
+        .partition(|(_, line)| line_height(line) < options.upscale_below);
+    let normal_rects: Vec<_> = normal.iter().map(|(_, line)| (*line).clone()).collect();
+    let (recognized, upscaled) = rayon::join(
+        || engine.recognize_text(ocr_input, &normal_rects),
+        || {
+            small
+                .par_iter()
+                .map(|(_, line)| recognize_upscaled(engine, image, line))
+                .collect::<Result<Vec<_>>>()
+        },
+    );
+    let normal = normal
+        .iter()
+        .zip(recognized?)
+        .map(|((index, _), line)| (*index, line.map(|line| to_line(&line, Affine::IDENTITY))));
+    let small = small
+        .iter()
+        .zip(upscaled?)
+        .map(|((index, _), line)| (*index, line));
+
+    // collected back in reading order
+    let mut lines: Vec<_> = normal.chain(small).collect();
+    lines.sort_by_key(|(index, _)| *index);
+    Ok(lines.into_iter().map(|(_, line)| line).collect())
+}
+
+// The median height of the words of a line
+fn line_height(words: &[rten_imageproc::RotatedRect]) -> f64 {
+    let mut heights: Vec<_> = words.iter().map(|r| r.height() as f64).collect();
+    heights.sort_by(f64::total_cmp);
+    heights.get(heights.len() / 2).copied().unwrap_or(0.0)
+}
+
+// Recognize a line of small text from its crop of the image upscaled to a
+// readable height, only the small lines are upscaled to keep the memory low
+fn recognize_upscaled(
+    engine: &OcrEngine,
+    image: &RgbaImage,
+    words: &[rten_imageproc::RotatedRect],
+) -> Result<Option<Line>> {
+    let height = line_height(words);
+    let Some(bounds) = rten_imageproc::bounding_rect(words.iter()) else {
+        return Ok(None);
+    };
+    // the boxes of the words may cut the ascenders and descenders
+    let margin = height as f32;
+    let (width, image_height) = image.dimensions();
+    let (x0, y0) = (
+        (bounds.left() - margin).max(0.0),
+        (bounds.top() - margin).max(0.0),
+    );
+    let x1 = (bounds.right() + margin).min(width as f32);
+    let y1 = (bounds.bottom() + margin).min(image_height as f32);
+    if height <= 0.0 || x1 <= x0 || y1 <= y0 {
+        return Ok(None);
+    }
+    let (x0, y0) = (x0 as u32, y0 as u32);
+    let (w, h) = (x1.ceil() as u32 - x0, y1.ceil() as u32 - y0);
+    let scale = (UPSCALED_TEXT_HEIGHT / height).min(MAX_UPSCALE);
+    let crop = image::imageops::crop_imm(image, x0, y0, w, h).to_image();
+    let upscaled = image::imageops::resize(
+        &crop,
+        (w as f64 * scale).round() as u32,
+        (h as f64 * scale).round() as u32,
+        FilterType::Lanczos3,
+    );
+    let img_source = ImageSource::from_bytes(upscaled.as_raw(), upscaled.dimensions())?;
+    let ocr_input = engine.prepare_input(img_source)?;
+
+    let s = scale as f32;
+    let scaled: Vec<_> = words
+        .iter()
+        .map(|rect| {
+            let center = rect.center();
+            let center = PointF::from_yx((center.y - y0 as f32) * s, (center.x - x0 as f32) * s);
+            rten_imageproc::RotatedRect::new(
+                center,
+                rect.up_axis(),
+                rect.width() * s,
+                rect.height() * s,
+            )
+        })
+        .collect();
+    let back = Affine::translate((x0 as f64, y0 as f64)) * Affine::scale(1.0 / scale);
+    let line = engine
+        .recognize_text(&ocr_input, &[scaled])?
+        .pop()
+        .flatten();
+    Ok(line.map(|line| to_line(&line, back)))
+}
+
+// The words of a recognized line, mapped by the transform
+fn to_line(line: &TextLine, transform: Affine) -> Line {
+    Line {
+        words: line
+            .words()
+            .map(|word| Word {
+                text: word.to_string(),
+                rect: transform * RotatedRect::from(word.rotated_rect()),
+            })
+            .collect(),
+    }
+}
+
+fn detect(
+    engine: &OcrEngine,
+    image: &RgbaImage,
+) -> Result<(OcrInput, Vec<rten_imageproc::RotatedRect>)> {
+    let img_source = ImageSource::from_bytes(image.as_raw(), image.dimensions())?;
+    let ocr_input = engine.prepare_input(img_source)?;
+    let word_rects = engine.detect_words(&ocr_input)?;
+    Ok((ocr_input, word_rects))
+}
+
+// The median clockwise angle of the words, in radians between -45 and 45 degrees
+fn skew(word_rects: &[rten_imageproc::RotatedRect]) -> f64 {
+    use std::f64::consts::FRAC_PI_2;
+    let mut angles: Vec<_> = word_rects
+        .iter()
+        .map(|rect| {
+            let up = rect.up_axis();
+            let angle = (up.x as f64).atan2(-up.y as f64);
+            // the boxes of a word may point up along any of their sides
+            angle - FRAC_PI_2 * (angle / FRAC_PI_2).round()
+        })
+        .collect();
+    angles.sort_by(f64::total_cmp);
+    angles.get(angles.len() / 2).copied().unwrap_or(0.0)
+}
+
+// The share of the characters in words made of letters or digits only,
+// the text read upside down or sideways is mostly fragments and symbols
+fn plausibility(lines: &[Line]) -> f64 {
+    let (mut plausible, mut total) = (0, 0);
+    for word in lines.iter().flat_map(|line| &line.words) {
+        let chars = word.text.chars().count();
+        let trimmed = word.text.trim_matches(|c: char| c.is_ascii_punctuation());
+        if trimmed.chars().count() > 1 && trimmed.chars().all(char::is_alphanumeric) {
+            plausible += chars;
+        }
+        total += chars;
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    plausible as f64 / total as f64
+}
+
+/// Capture a region of the screen and recognize its words
+pub fn recognize_region(rect: Rect) -> Result<OcrResult> {
+    let blob = capture::screen_rect(rect)?;
+    let dimensions = (rect.width().abs() as u32, rect.height().abs() as u32);
+    let mut result = recognize(blob.data(), dimensions)?;
+    result.origin = Point::new(rect.min_x(), rect.min_y());
+    set_last_result(&result);
+    Ok(result)
+}
+
+/// Recognize the words of an image file
+pub fn recognize_file(path: &Path) -> Result<OcrResult> {
+    let image = image::open(path)
+        .with_context(|| format!("cannot open {}", path.display()))?
+        .to_rgba8();
+    let result = recognize(image.as_raw(), image.dimensions())?;
+    set_last_result(&result);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+
+    fn line(words: &[(&str, f64, f64)]) -> Line {
+        let result = OcrResult::from_boxes(&[&words
+            .iter()
+            .map(|&(text, x0, x1)| (text, Rect::new(x0, 100.0, x1, 120.0)))
+            .collect::<Vec<_>>()]);
+        result.lines.into_iter().next().unwrap()
+    }
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(Line::text).collect()
+    }
+
+    #[test]
+    fn join_cut_line() {
+        // two tiles of 1000 pixels overlapping from 904 to 1000
+        let tiles = [(0, 0), (904, 0)];
+        let left = vec![line(&[
+            ("The", 850.0, 890.0),
+            ("quick", 910.0, 960.0),
+            ("bro", 970.0, 999.0),
+        ])];
+        let right = vec![
+            line(&[
+                ("quick", 910.0, 960.0),
+                ("brown", 970.0, 1030.0),
+                ("fox", 1040.0, 1080.0),
+            ]),
+            // a separate column
+            line(&[("jumps", 1200.0, 1260.0)]),
+        ];
+        let lines = join_tiles(&tiles, vec![left, right], 1000, (1904, 500));
+        assert_eq!(texts(&lines), ["The quick brown fox", "jumps"]);
+    }
+
+    // Lines of words 40 pixels wide starting at x, from the given height down
+    fn column(x: f64, y: f64, lines: &[&[&'static str]]) -> Vec<Line> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(row, words)| {
+                let boxes: Vec<_> = words
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &text)| {
+                        let (x0, y0) = (x + index as f64 * 50.0, y + row as f64 * 30.0);
+                        (text, Rect::new(x0, y0, x0 + 40.0, y0 + 20.0))
+                    })
+                    .collect();
+                OcrResult::from_boxes(&[&boxes]).lines.remove(0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn columns_of_tiles() {
+        // a column of text in each tile, and a title across both
+        let tiles = [(0, 0), (904, 0)];
+        let mut left = column(850.0, 100.0, &[&["Two", "column"]]);
+        left.extend(column(100.0, 200.0, &[&["left", "one"], &["left", "two"]]));
+        let right = [
+            column(950.0, 100.0, &[&["column", "title"]]),
+            column(1100.0, 200.0, &[&["right", "one"], &["right", "two"]]),
+        ]
+        .concat();
+        let engine = OcrEngine::new(OcrEngineParams::default()).unwrap();
+        let lines = join_tiles(&tiles, vec![left, right], 1000, (1904, 500));
+        let lines = reading_order(&engine, lines).unwrap();
+        assert_eq!(
+            texts(&lines),
+            [
+                "Two column column title",
+                "left one",
+                "left two",
+                "right one",
+                "right two"
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn tiled_columns() {
+        let left = ["The left column", "is read first", "from top to bottom"];
+        let right = ["then the right", "column follows", "in the same way"];
+        let texts: Vec<_> = (0..3)
+            .flat_map(|row| {
+                [
+                    (0, row * 40, left[row as usize]),
+                    (700, row * 40, right[row as usize]),
+                ]
+            })
+            .collect();
+        let image = fixtures::render_at(&texts, Style::default());
+        let read = |tile_size| {
+            let options = OcrOptions {
+                tile_size,
+                ..OcrOptions::default()
+            };
+            let result = recognize_with(image.as_raw(), image.dimensions(), &options).unwrap();
+            result.text()
+        };
+        assert_eq!(read(0), [left, right].concat().join("\n"));
+        // the tiles cut the right column
+        assert_eq!(read(800), read(0));
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn streamed_lines() {
+        let lines = [
+            "The lines are given",
+            "one after the other",
+            "in reading order",
+        ];
+        let image = fixtures::render(&lines, Style::default());
+        let options = OcrOptions::default();
+        let prepared = prepare(image.as_raw(), image.dimensions(), &options).unwrap();
+        let mut streamed = vec![];
+        let result =
+            recognize_streamed(&prepared, &options, |line| streamed.push(line.text())).unwrap();
+        assert_eq!(streamed, lines);
+        assert_eq!(texts(&result.lines), lines);
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn small_text() {
+        let lines = ["Small text is read from", "upscaled crops of its lines"];
+        let style = Style {
+            size: 9.0,
+            ..Style::default()
+        };
+        let image = fixtures::render(&lines, style);
+        let options = OcrOptions {
+            upscale_below: 16.0,
+            ..OcrOptions::default()
+        };
+        let result = recognize_with(image.as_raw(), image.dimensions(), &options).unwrap();
+        assert_eq!(result.text(), lines.join("\n"));
+        // the boxes are mapped back to the image
+        let first = Rect::from(&result.lines[0].words[0].rect);
+        assert!(first.x0 >= 0.0 && first.x1 < image.width() as f64 / 2.0);
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use vello::kurbo::Point;
+
+/// Move the pointer to a point of the screen and press the left button,
+/// twice for a double click
+#[cfg(target_os = "linux")]
+pub fn click(point: Point, count: u32) -> Result<()> {
+    use anyhow::bail;
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xproto::{BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT, MOTION_NOTIFY_EVENT};
+    use x11rb::protocol::xtest::{self, ConnectionExt};
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::CURRENT_TIME;
+
+    let (conn, screen_num) = x11rb::connect(None)?;
+    if conn
+        .extension_information(xtest::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        bail!("the X server has no XTest extension");
+    }
+    let root = conn.setup().roots[screen_num].root;
+    let (x, y) = (point.x.round() as i16, point.y.round() as i16);
+
+    conn.xtest_fake_input(MOTION_NOTIFY_EVENT, 0, CURRENT_TIME, root, x, y, 0)?;
+    for _ in 0..count {
+        for event in [BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT] {
+            conn.xtest_fake_input(event, 1, CURRENT_TIME, root, 0, 0, 0)?;
+        }
+    }
+    // wait for the server to process the events before the connection closes
+    conn.sync()?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn click(_point: Point, _count: u32) -> Result<()> {
+    anyhow::bail!("clicking is only supported on X11")
+}
@@ -0,0 +1,617 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use image::RgbaImage;
+use vello::kurbo::{Affine, Point};
+
+use std::str::FromStr;
+
+/// A step of the preprocessing run on the capture before the recognition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Grayscale,
+    /// Spread the luminance between the darkest and brightest pixels
+    Stretch,
+    /// Raise the channels to this power, above 1 darkens the image
+    Gamma(f32),
+    Sharpen,
+    /// Binarize with a global threshold computed from the histogram
+    Otsu,
+    /// Binarize with a threshold computed in a window around each pixel
+    Sauvola(u32),
+}
+
+impl FromStr for Step {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (s.trim(), None),
+        };
+        let step = match (name, value) {
+            ("grayscale", None) => Self::Grayscale,
+            ("stretch", None) => Self::Stretch,
+            ("gamma", value) => Self::Gamma(value.unwrap_or("1.5").parse()?),
+            ("sharpen", None) => Self::Sharpen,
+            ("otsu", None) => Self::Otsu,
+            ("sauvola", value) => Self::Sauvola(value.unwrap_or("15").parse()?),
+            ("grayscale" | "stretch" | "sharpen" | "otsu", Some(_)) => {
+                bail!("the step `{name}` takes no value")
+            }
+            _ => bail!("unknown step `{name}`"),
+        };
+        if matches!(step, Self::Gamma(g) if !g.is_finite()) {
+            bail!("the value of `{name}` must be a finite number");
+        }
+        if matches!(step, Self::Gamma(g) if g <= 0.0) || step == Self::Sauvola(0) {
+            bail!("the value of `{name}` must be positive");
+        }
+        Ok(step)
+    }
+}
+
+/// When the colours of the capture are inverted to get dark text on a light
+/// background
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum Invert {
+    /// Invert when the text looks lighter than the background
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Invert the image when the text is light on a dark background, or as the
+/// override says
+pub fn invert(image: &mut RgbaImage, invert: Invert) {
+    let inverted = match invert {
+        Invert::Auto => is_light_on_dark(image),
+        Invert::Always => true,
+        Invert::Never => false,
+    };
+    if inverted {
+        map_pixels(image, |rgb| rgb.map(|c| 255 - c));
+    }
+}
+
+// The background covers more pixels than the text, so the larger side of
+// the luminance threshold is the background
+fn is_light_on_dark(image: &RgbaImage) -> bool {
+    let histogram = histogram(image);
+    let threshold = otsu_threshold(image) as usize;
+    let (dark, light) = histogram.split_at(threshold + 1);
+    dark.iter().sum::<u32>() > light.iter().sum::<u32>()
+}
+
+/// The clockwise quarter turns that put the text of a capture upright
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    #[default]
+    #[value(name = "0")]
+    None,
+    #[value(name = "90")]
+    Right,
+    #[value(name = "180")]
+    Down,
+    #[value(name = "270")]
+    Left,
+    /// Try the other orientations when the text reads poorly
+    Auto,
+}
+
+impl Rotation {
+    fn quarter_turns(self) -> i32 {
+        match self {
+            Self::None | Self::Auto => 0,
+            Self::Right => 1,
+            Self::Down => 2,
+            Self::Left => 3,
+        }
+    }
+
+    /// Turn further by quarter turns, clockwise when positive
+    pub fn turned(self, quarter_turns: i32) -> Self {
+        match (self.quarter_turns() + quarter_turns).rem_euclid(4) {
+            1 => Self::Right,
+            2 => Self::Down,
+            3 => Self::Left,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Turn the image, the transform maps the turned image back to the original
+pub fn turn(image: &RgbaImage, rotation: Rotation) -> (RgbaImage, Affine) {
+    let (w, h) = (image.width() as f64, image.height() as f64);
+    match rotation {
+        Rotation::None | Rotation::Auto => (image.clone(), Affine::IDENTITY),
+        Rotation::Right => (
+            image::imageops::rotate90(image),
+            Affine::new([0.0, -1.0, 1.0, 0.0, 0.0, h]),
+        ),
+        Rotation::Down => (
+            image::imageops::rotate180(image),
+            Affine::new([-1.0, 0.0, 0.0, -1.0, w, h]),
+        ),
+        Rotation::Left => (
+            image::imageops::rotate270(image),
+            Affine::new([0.0, 1.0, -1.0, 0.0, w, 0.0]),
+        ),
+    }
+}
+
+/// Rotate the image clockwise by an angle in radians around its centre,
+/// the corners uncovered are white; the transform maps the rotated image
+/// back to the original
+pub fn rotate(image: &RgbaImage, angle: f64) -> (RgbaImage, Affine) {
+    let (width, height) = image.dimensions();
+    let center = Point::new(width as f64 / 2.0, height as f64 / 2.0);
+    let back = Affine::rotate_about(-angle, center);
+    let rotated = RgbaImage::from_fn(width, height, |x, y| {
+        let p = back * Point::new(x as f64 + 0.5, y as f64 + 0.5);
+        image::Rgba(bilinear(image, p.x - 0.5, p.y - 0.5))
+    });
+    (rotated, back)
+}
+
+fn bilinear(image: &RgbaImage, x: f64, y: f64) -> [u8; 4] {
+    let (width, height) = image.dimensions();
+    let pixel = |x: f64, y: f64| {
+        if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+            [255; 4]
+        } else {
+            image.get_pixel(x as u32, y as u32).0
+        }
+    };
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let corners = [
+        (pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (pixel(x0 + 1.0, y0), fx * (1.0 - fy)),
+        (pixel(x0, y0 + 1.0), (1.0 - fx) * fy),
+        (pixel(x0 + 1.0, y0 + 1.0), fx * fy),
+    ];
+    std::array::from_fn(|c| {
+        corners
+            .iter()
+            .map(|(p, weight)| p[c] as f64 * weight)
+            .sum::<f64>()
+            .round() as u8
+    })
+}
+
+/// The box of the pixels that differ from the background, the most common
+/// luminance, grown by the padding; `None` when the image is uniform
+pub fn content_bounds(image: &RgbaImage, padding: u32) -> Option<[u32; 4]> {
+    const MIN_CONTRAST: u8 = 24;
+    let histogram = histogram(image);
+    let background = (0..=255)
+        .max_by_key(|&v| histogram[v as usize])
+        .unwrap_or(0);
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let [r, g, b, _] = pixel.0;
+        if luma([r, g, b]).abs_diff(background) > MIN_CONTRAST {
+            (x0, y0) = (x0.min(x), y0.min(y));
+            (x1, y1) = (x1.max(x + 1), y1.max(y + 1));
+        }
+    }
+    if x0 >= x1 {
+        return None;
+    }
+    let (width, height) = image.dimensions();
+    Some([
+        x0.saturating_sub(padding),
+        y0.saturating_sub(padding),
+        (x1 + padding).min(width),
+        (y1 + padding).min(height),
+    ])
+}
+
+/// Keep the pixels close to the text color as black on white, the
+/// tolerance is the distance between the colors
+pub fn isolate_color(image: &mut RgbaImage, color: [u8; 3], tolerance: f64) {
+    map_pixels(image, |rgb| {
+        let distance = rgb
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (a as f64 - b as f64).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        [binary(distance > tolerance); 3]
+    });
+}
+
+/// Parse a color written as "RRGGBB", with or without a leading "#"
+pub fn parse_color(s: &str) -> Result<[u8; 3]> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        bail!("expected a color as RRGGBB");
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Run the steps on the image in order
+pub fn apply(image: &mut RgbaImage, steps: &[Step]) {
+    for step in steps {
+        match *step {
+            Step::Grayscale => map_pixels(image, |[r, g, b]| {
+                let l = luma([r, g, b]);
+                [l, l, l]
+            }),
+            Step::Stretch => stretch(image),
+            Step::Gamma(gamma) => {
+                let table: Vec<u8> = (0..=255)
+                    .map(|v| (255.0 * (v as f32 / 255.0).powf(gamma)).round() as u8)
+                    .collect();
+                map_pixels(image, |rgb| rgb.map(|c| table[c as usize]));
+            }
+            Step::Sharpen => *image = image::imageops::unsharpen(image, 1.0, 0),
+            Step::Otsu => {
+                let threshold = otsu_threshold(image);
+                map_pixels(image, |rgb| [binary(luma(rgb) > threshold); 3]);
+            }
+            Step::Sauvola(window) => sauvola(image, window),
+        }
+    }
+}
+
+fn map_pixels(image: &mut RgbaImage, f: impl Fn([u8; 3]) -> [u8; 3]) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [r, g, b] = f([r, g, b]);
+        pixel.0 = [r, g, b, a];
+    }
+}
+
+pub fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+}
+
+fn binary(white: bool) -> u8 {
+    if white {
+        255
+    } else {
+        0
+    }
+}
+
+pub fn histogram(image: &RgbaImage) -> [u32; 256] {
+    let mut histogram = [0; 256];
+    for pixel in image.pixels() {
+        let [r, g, b, _] = pixel.0;
+        histogram[luma([r, g, b]) as usize] += 1;
+    }
+    histogram
+}
+
+// Map the 1st and 99th percentiles of the luminance to black and white
+fn stretch(image: &mut RgbaImage) {
+    let histogram = histogram(image);
+    let total: u32 = histogram.iter().sum();
+    let percentile = |p: u32| {
+        let mut count = 0;
+        histogram
+            .iter()
+            .position(|&n| {
+                count += n;
+                count * 100 > total * p
+            })
+            .unwrap_or(255) as f32
+    };
+    let (low, high) = (percentile(1), percentile(99));
+    if high <= low {
+        return;
+    }
+    map_pixels(image, |rgb| {
+        rgb.map(|c| ((c as f32 - low) * 255.0 / (high - low)).clamp(0.0, 255.0) as u8)
+    });
+}
+
+// The luminance threshold maximizing the variance between the two classes
+fn otsu_threshold(image: &RgbaImage) -> u8 {
+    let histogram = histogram(image);
+    let total: f64 = histogram.iter().map(|&n| n as f64).sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(v, &n)| v as f64 * n as f64)
+        .sum();
+    let (mut weight, mut partial, mut best, mut threshold) = (0.0, 0.0, 0.0, 0);
+    for (v, &n) in histogram.iter().enumerate() {
+        weight += n as f64;
+        partial += v as f64 * n as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        let mean_low = partial / weight;
+        let mean_high = (sum - partial) / (total - weight);
+        let variance = weight * (total - weight) * (mean_low - mean_high).powi(2);
+        if variance > best {
+            best = variance;
+            threshold = v as u8;
+        }
+    }
+    threshold
+}
+
+// Sauvola's threshold from the mean and deviation of the luminance in a
+// window, computed with integral images
+fn sauvola(image: &mut RgbaImage, window: u32) {
+    const K: f64 = 0.3;
+    let (width, height) = image.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    let mut sums = vec![0.0; (w + 1) * (h + 1)];
+    let mut squares = vec![0.0; (w + 1) * (h + 1)];
+    for y in 0..h {
+        for x in 0..w {
+            let [r, g, b, _] = image.get_pixel(x as u32, y as u32).0;
+            let l = luma([r, g, b]) as f64;
+            let i = (y + 1) * (w + 1) + x + 1;
+            sums[i] = l + sums[i - 1] + sums[i - w - 1] - sums[i - w - 2];
+            squares[i] = l * l + squares[i - 1] + squares[i - w - 1] - squares[i - w - 2];
+        }
+    }
+    let area = |table: &[f64], x0: usize, y0: usize, x1: usize, y1: usize| {
+        table[y1 * (w + 1) + x1] - table[y0 * (w + 1) + x1] - table[y1 * (w + 1) + x0]
+            + table[y0 * (w + 1) + x0]
+    };
+
+    let half = window as usize / 2;
+    for y in 0..h {
+        for x in 0..w {
+            let (x0, y0) = (x.saturating_sub(half), y.saturating_sub(half));
+            let (x1, y1) = ((x + half + 1).min(w), (y + half + 1).min(h));
+            let n = ((x1 - x0) * (y1 - y0)) as f64;
+            let mean = area(&sums, x0, y0, x1, y1) / n;
+            let deviation = (area(&squares, x0, y0, x1, y1) / n - mean * mean)
+                .max(0.0)
+                .sqrt();
+            let threshold = mean * (1.0 + K * (deviation / 128.0 - 1.0));
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            let [r, g, b, a] = pixel.0;
+            let v = binary(luma([r, g, b]) as f64 > threshold);
+            pixel.0 = [v, v, v, a];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+    use crate::ocr::{self, OcrOptions};
+    use image::Rgba;
+
+    fn gray(width: u32, height: u32, f: impl Fn(u32, u32) -> u8) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let v = f(x, y);
+            Rgba([v, v, v, 255])
+        })
+    }
+
+    fn luma_at(image: &RgbaImage, x: u32, y: u32) -> u8 {
+        let [r, g, b, _] = image.get_pixel(x, y).0;
+        luma([r, g, b])
+    }
+
+    #[test]
+    fn parse_steps() {
+        let steps: Vec<Step> = ["grayscale", " stretch ", "gamma", "gamma=2.2", "sharpen"]
+            .iter()
+            .chain(&["otsu", "sauvola", "sauvola = 31"])
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                Step::Grayscale,
+                Step::Stretch,
+                Step::Gamma(1.5),
+                Step::Gamma(2.2),
+                Step::Sharpen,
+                Step::Otsu,
+                Step::Sauvola(15),
+                Step::Sauvola(31),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_step_errors() {
+        for (s, error) in [
+            ("foo", "unknown step `foo`"),
+            ("foo=1", "unknown step `foo`"),
+            ("otsu=3", "the step `otsu` takes no value"),
+            ("gamma=0", "the value of `gamma` must be positive"),
+            ("gamma=-1.5", "the value of `gamma` must be positive"),
+            ("gamma=NaN", "the value of `gamma` must be a finite number"),
+            ("gamma=inf", "the value of `gamma` must be a finite number"),
+            ("gamma=-inf", "the value of `gamma` must be a finite number"),
+            ("sauvola=0", "the value of `sauvola` must be positive"),
+            ("sauvola=-1", "invalid digit found in string"),
+            ("gamma=x", "invalid float literal"),
+        ] {
+            assert_eq!(s.parse::<Step>().unwrap_err().to_string(), error, "{s}");
+        }
+    }
+
+    #[test]
+    fn grayscale() {
+        let mut image = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 128]));
+        apply(&mut image, &[Step::Grayscale]);
+        assert!(image.pixels().all(|p| p.0 == [76, 76, 76, 128]));
+    }
+
+    #[test]
+    fn stretch_contrast() {
+        let mut image = gray(10, 10, |x, _| if x < 5 { 100 } else { 150 });
+        apply(&mut image, &[Step::Stretch]);
+        assert_eq!(luma_at(&image, 0, 0), 0);
+        assert_eq!(luma_at(&image, 9, 9), 255);
+    }
+
+    #[test]
+    fn gamma() {
+        let mut image = gray(1, 1, |_, _| 128);
+        apply(&mut image, &[Step::Gamma(2.0)]);
+        assert_eq!(luma_at(&image, 0, 0), 64);
+    }
+
+    #[test]
+    fn sharpen_edges() {
+        let mut image = gray(20, 20, |x, _| if x < 10 { 100 } else { 150 });
+        apply(&mut image, &[Step::Sharpen]);
+        assert!(luma_at(&image, 9, 10) < 100);
+        assert!(luma_at(&image, 10, 10) > 150);
+        assert_eq!(luma_at(&image, 0, 10), 100);
+    }
+
+    #[test]
+    fn otsu() {
+        let mut image = gray(16, 16, |x, y| match (x + y) % 2 {
+            0 => 40 + (x % 3) as u8,
+            _ => 200 - (y % 5) as u8,
+        });
+        apply(&mut image, &[Step::Otsu]);
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let expected = if (x + y) % 2 == 0 { 0 } else { 255 };
+            assert_eq!(pixel.0, [expected, expected, expected, 255]);
+        }
+    }
+
+    #[test]
+    fn sauvola_uneven_light() {
+        // a dark line on a background getting lighter from left to right
+        let mut image = gray(60, 30, |x, y| {
+            let background = 120 + x as u8;
+            if y == 15 || y == 16 {
+                background - 80
+            } else {
+                background
+            }
+        });
+        apply(&mut image, &[Step::Sauvola(15)]);
+        for x in 0..60 {
+            assert_eq!(luma_at(&image, x, 15), 0);
+            assert_eq!(luma_at(&image, x, 16), 0);
+            assert_eq!(luma_at(&image, x, 0), 255);
+            assert_eq!(luma_at(&image, x, 29), 255);
+        }
+    }
+
+    #[test]
+    fn bounds_of_the_content() {
+        let mut image = gray(20, 20, |x, y| if x == 0 && y == 0 { 240 } else { 255 });
+        assert_eq!(content_bounds(&image, 2), None);
+        for (x, y) in [(5, 6), (7, 8)] {
+            image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+        }
+        assert_eq!(content_bounds(&image, 0), Some([5, 6, 8, 9]));
+        assert_eq!(content_bounds(&image, 2), Some([3, 4, 10, 11]));
+        assert_eq!(content_bounds(&image, 10), Some([0, 0, 18, 19]));
+    }
+
+    #[test]
+    fn bounds_on_a_dark_background() {
+        let mut image = gray(10, 10, |_, _| 30);
+        image.put_pixel(9, 4, Rgba([220, 220, 220, 255]));
+        assert_eq!(content_bounds(&image, 1), Some([8, 3, 10, 6]));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#ff8000").unwrap(), [255, 128, 0]);
+        assert_eq!(parse_color("FF8000").unwrap(), [255, 128, 0]);
+        assert!(parse_color("ff80").is_err());
+        assert!(parse_color("gg8000").is_err());
+    }
+
+    const TEXT: &[&str] = &["Light text on a dark background", "reads as well"];
+
+    fn light_on_dark() -> Style {
+        Style {
+            foreground: [230, 230, 200],
+            background: [20, 30, 60],
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn light_text_detected() {
+        let dark_on_light = fixtures::render(TEXT, Style::default());
+        let light_on_dark = fixtures::render(TEXT, light_on_dark());
+        assert!(!is_light_on_dark(&dark_on_light));
+        assert!(is_light_on_dark(&light_on_dark));
+    }
+
+    #[test]
+    fn invert_auto() {
+        let dark_on_light = fixtures::render(TEXT, Style::default());
+        let mut image = dark_on_light.clone();
+        invert(&mut image, Invert::Auto);
+        assert!(image == dark_on_light);
+
+        let white_on_black = Style {
+            foreground: [255; 3],
+            background: [0; 3],
+            ..Style::default()
+        };
+        let mut image = fixtures::render(TEXT, white_on_black);
+        invert(&mut image, Invert::Auto);
+        // the antialiasing of the two renders rounds differently
+        let difference = image
+            .as_raw()
+            .iter()
+            .zip(dark_on_light.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max();
+        assert!(difference <= Some(1));
+    }
+
+    #[test]
+    fn invert_overrides() {
+        let dark_on_light = fixtures::render(TEXT, Style::default());
+        let mut image = dark_on_light.clone();
+        invert(&mut image, Invert::Never);
+        assert!(image == dark_on_light);
+        invert(&mut image, Invert::Always);
+        assert!(is_light_on_dark(&image));
+
+        let light_on_dark = fixtures::render(TEXT, light_on_dark());
+        let mut image = light_on_dark.clone();
+        invert(&mut image, Invert::Never);
+        assert!(image == light_on_dark);
+    }
+
+    // The share of the words of the fixture read from the image
+    fn words_read(image: &RgbaImage, invert: Invert) -> f64 {
+        let options = OcrOptions {
+            invert,
+            ..OcrOptions::default()
+        };
+        let result = ocr::recognize_with(image.as_raw(), image.dimensions(), &options).unwrap();
+        let text = result.text();
+        let read: Vec<_> = text.split_whitespace().collect();
+        let expected: Vec<_> = TEXT
+            .iter()
+            .flat_map(|line| line.split_whitespace())
+            .collect();
+        let found = expected.iter().filter(|word| read.contains(word)).count();
+        found as f64 / expected.len() as f64
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn recognize_light_on_dark() {
+        let dark_on_light = fixtures::render(TEXT, Style::default());
+        assert_eq!(words_read(&dark_on_light, Invert::Auto), 1.0);
+
+        let light_on_dark = fixtures::render(TEXT, light_on_dark());
+        let inverted = words_read(&light_on_dark, Invert::Auto);
+        assert_eq!(inverted, 1.0);
+        // the engine reads dark text better
+        assert!(words_read(&light_on_dark, Invert::Never) < inverted);
+    }
+}
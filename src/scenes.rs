@@ -1,9 +1,10 @@
+use crate::clipboard::{self, CopyMode};
+use crate::layout;
+use crate::ocr::OcrResult;
 use crate::state::*;
 use std::f64::consts::PI;
 use std::sync::Arc;
 
-use copypasta::{ClipboardContext, ClipboardProvider};
-
 use vello::kurbo::{Affine, CircleSegment, PathEl, Point, Rect, Stroke, TranslateScale};
 use vello::peniko::{Blob, Color, Fill, Font, Format::*, Image};
 use vello::skrifa::instance::LocationRef;
@@ -15,6 +16,10 @@ use winit::window::CursorIcon;
 
 const ROBOTO_FONT: &[u8] = include_bytes!("../assets/Roboto-Regular.ttf");
 
+const TOOLBAR_BTN_HEIGHT: f64 = 32.0;
+const TOOLBAR_PADDING: f64 = 8.0;
+const TOOLBAR_FONT_SIZE: f32 = 16.0;
+
 pub fn draw(state: &mut AppState, view: &mut View) {
     let scene = &mut view.scene;
     match state.page {
@@ -139,6 +144,45 @@ pub fn draw(state: &mut AppState, view: &mut View) {
                 return;
             }
 
+            // Lay out the toolbar above the image
+            let mode_label = format!("Mode: {}", state.settings.text_mode.name());
+            let buttons = [
+                (COPY_BTN, "Copy"),
+                (COPY_TABLE_BTN, "Copy as table"),
+                (COPY_PARAGRAPHS_BTN, "Copy as paragraphs"),
+                (UNDO_COPY_BTN, "Undo copy"),
+                (TEXT_MODE_BTN, mode_label.as_str()),
+                (
+                    SHOW_ORDER_BTN,
+                    if page_data.show_order {
+                        "Hide order"
+                    } else {
+                        "Show order"
+                    },
+                ),
+                (
+                    PREVIEW_BTN,
+                    if page_data.show_preview {
+                        "Show original"
+                    } else {
+                        "Show processed"
+                    },
+                ),
+                (ROTATE_LEFT_BTN, "Rotate left"),
+                (ROTATE_RIGHT_BTN, "Rotate right"),
+                (
+                    TEXT_COLOR_BTN,
+                    match (page_data.picking_color, page_data.text_color) {
+                        (true, _) => "Click the text color",
+                        (false, Some(_)) => "Clear text color",
+                        (false, None) => "Pick text color",
+                    },
+                ),
+            ];
+            let toolbar_height = layout_toolbar(&mut view.elems, &buttons, state.screen_width);
+            let image_rect =
+                Rect::new(0.0, toolbar_height, state.screen_width, state.screen_height);
+
             // Get the transformation for the image and scene elements
             let img_width = page_data.rect.width().abs();
             let img_height = page_data.rect.height().abs();
@@ -148,28 +192,67 @@ pub fn draw(state: &mut AppState, view: &mut View) {
                 img_width as u32,
                 img_height as u32,
             );
-            let scale = (state.screen_width / img_width).min(state.screen_height / img_height);
+            let scale = (image_rect.width() / img_width).min(image_rect.height() / img_height);
             let iw = img_width * scale;
             let ih = img_height * scale;
             let transform = Affine::translate((
-                (state.screen_width - iw) / 2.0,
-                (state.screen_height - ih) / 2.0,
+                (image_rect.width() - iw) / 2.0,
+                image_rect.y0 + (image_rect.height() - ih) / 2.0,
             )) * Affine::scale(scale);
 
+            page_data.image_transform = transform;
             background(scene, screen_rect, Color::rgba8(16, 16, 16, 255));
-            scene.draw_image(&image, transform);
+            match page_data.preview {
+                Some((ref preview, preview_transform)) if page_data.show_preview => {
+                    scene.draw_image(preview, transform * preview_transform)
+                }
+                _ => scene.draw_image(&image, transform),
+            }
+            for (index, label) in buttons {
+                toolbar_btn(scene, view.elems[index], label);
+            }
 
             if !page_data.extracted {
                 state.redraw = true;
-                spinner(scene, screen_rect, page_data.time.elapsed().as_secs_f64());
                 let mut static_elems = EXTRACTED_ELEMS.lock().unwrap();
-                if let Some((ref rects, ref extracted_text)) = *static_elems {
-                    view.elems.push(ViewElement {
-                        bound: screen_rect,
-                        active: true,
-                        cursor: CursorIcon::Crosshair,
-                        ..Default::default()
+                // the words of the lines read so far
+                if static_elems.latest == Some(page_data.time) {
+                    let fill_color = Color::rgba8(0, 116, 255, 50);
+                    for word in static_elems.lines.iter().flat_map(|line| &line.words) {
+                        scene.fill(Fill::NonZero, transform, fill_color, None, &word.rect);
+                    }
+                }
+                spinner(scene, image_rect, page_data.time.elapsed().as_secs_f64());
+                // skip the results of the previous captures
+                let result = match static_elems.result.take() {
+                    Some((time, result, prepared)) if time == page_data.time => {
+                        Some((result, prepared))
+                    }
+                    _ => None,
+                };
+                if let Some((result, prepared)) = result {
+                    // the window stays open on the capture without any text
+                    // when the extraction failed
+                    let (result, error) = match result {
+                        Ok(result) => (result, None),
+                        Err(err) => (OcrResult::default(), Some(err)),
+                    };
+                    let captured = Rect::new(0.0, 0.0, img_width, img_height);
+                    page_data.trimmed = prepared
+                        .as_ref()
+                        .map(|prepared| {
+                            let (width, height) = prepared.image.dimensions();
+                            let bounds = Rect::new(0.0, 0.0, width as f64, height as f64);
+                            prepared.transform.transform_rect_bbox(bounds)
+                        })
+                        .filter(|bounds| *bounds != captured);
+                    page_data.preview = prepared.map(|prepared| {
+                        let (width, height) = prepared.image.dimensions();
+                        let blob = Blob::new(Arc::new(prepared.image.into_raw()));
+                        (Image::new(blob, Rgba8, width, height), prepared.transform)
                     });
+                    let extracted_text = layout::text(&result, state.settings.text_mode);
+                    let rects: Vec<_> = result.words().map(|word| word.rect).collect();
                     for _ in 0..rects.len() {
                         view.elems.push(ViewElement {
                             active: true,
@@ -177,12 +260,32 @@ pub fn draw(state: &mut AppState, view: &mut View) {
                             ..Default::default()
                         });
                     }
-                    let mut ctx = ClipboardContext::new().unwrap();
-                    ctx.set_contents(extracted_text.to_owned()).unwrap();
-                    // page_data.text = extracted_text.to_string();
-                    page_data.rotated_rects = rects.to_vec();
+                    for elem in &mut view.elems[COPY_BTN..TEXT_RECTS] {
+                        elem.active = true;
+                    }
+                    if state.settings.copy == CopyMode::Auto && error.is_none() {
+                        if let Err(err) = clipboard::copy_text(
+                            state.settings.clipboard,
+                            &extracted_text,
+                            state.settings.clear_after(),
+                        ) {
+                            eprintln!("warning: cannot copy the text: {err:#}");
+                        }
+                    }
+                    for reply in state.pending_replies.drain(..) {
+                        let _ = reply.send(match &error {
+                            Some(err) => Err(err.clone()),
+                            None => Ok(result.clone()),
+                        });
+                    }
+                    page_data.text = extracted_text;
+                    page_data.blocks = layout::order::blocks(&result)
+                        .iter()
+                        .map(|block| block.rect)
+                        .collect();
+                    page_data.result = result;
+                    page_data.rotated_rects = rects;
                     page_data.extracted = true;
-                    *static_elems = None;
                 }
                 return;
             }
@@ -192,9 +295,9 @@ pub fn draw(state: &mut AppState, view: &mut View) {
                 let [scale, _, _, _, trans_x, trans_y] = transform.as_coeffs();
                 let trans_scale = TranslateScale::new((trans_x, trans_y).into(), scale);
                 let bound = trans_scale * rect;
-                // the view elements start with the screen rectangle then the text rectangles
-                view.elems[i + 1].bound = bound;
-                if view.elems[i + 1].mouse_enter {
+                // the view elements start with the extract area and the toolbar then the text rectangles
+                view.elems[TEXT_RECTS + i].bound = bound;
+                if view.elems[TEXT_RECTS + i].mouse_enter {
                     scene.fill(
                         Fill::NonZero,
                         transform,
@@ -205,10 +308,51 @@ pub fn draw(state: &mut AppState, view: &mut View) {
                 }
                 scene.fill(Fill::NonZero, transform, fill_color, None, rotated_rect);
             }
+            if let Some(trimmed) = page_data.trimmed {
+                scene.stroke(
+                    &Stroke::new(1.5).with_dashes(0.0, [6.0, 4.0]),
+                    Affine::IDENTITY,
+                    Color::rgba8(255, 255, 255, 200),
+                    None,
+                    &transform.transform_rect_bbox(trimmed),
+                );
+            }
+            if page_data.show_order {
+                for (i, block) in page_data.blocks.iter().enumerate() {
+                    block_order(scene, transform.transform_rect_bbox(*block), i + 1);
+                }
+            }
         }
     }
 }
 
+// Outline a text block and number it with its place in the reading order
+fn block_order(scene: &mut Scene, rect: Rect, number: usize) {
+    let color = Color::rgba8(255, 140, 0, 255);
+    scene.stroke(&Stroke::new(2.0), Affine::IDENTITY, color, None, &rect);
+
+    let font = Font::new(Blob::new(Arc::new(ROBOTO_FONT)), 0);
+    let label = number.to_string();
+    let (glyphs, text_width, _) = layout_text(&font, &label, TOOLBAR_FONT_SIZE);
+    let badge = Rect::new(
+        rect.x0,
+        rect.y0,
+        rect.x0 + text_width as f64 + TOOLBAR_PADDING,
+        rect.y0 + TOOLBAR_FONT_SIZE as f64 + 4.0,
+    );
+    scene.fill(Fill::NonZero, Affine::IDENTITY, color, None, &badge);
+    scene
+        .draw_glyphs(&font)
+        .transform(Affine::translate((
+            badge.x0 + TOOLBAR_PADDING / 2.0,
+            badge.y0 + TOOLBAR_FONT_SIZE as f64 * 0.9,
+        )))
+        .brush(Color::BLACK)
+        .font_size(TOOLBAR_FONT_SIZE)
+        .hint(false)
+        .draw(Fill::NonZero, glyphs.into_iter())
+}
+
 fn background(scene: &mut Scene, rect: Rect, color: Color) {
     scene.fill(Fill::NonZero, Affine::IDENTITY, color, None, &rect);
 }
@@ -235,28 +379,7 @@ fn confirm_btn(scene: &mut Scene, elem: ViewElement) {
     let stroke_color = Color::WHITE;
     let size = 24.0;
     let font = Font::new(Blob::new(Arc::new(ROBOTO_FONT)), 0);
-    let font_ref = to_font_ref(&font).unwrap();
-    let font_size = vello::skrifa::instance::Size::new(size);
-    let charmap = font_ref.charmap();
-    let glyph_metrics = font_ref.glyph_metrics(font_size, LocationRef::new(&[]));
-    let metrics = font_ref.metrics(font_size, LocationRef::new(&[]));
-    let line_height = metrics.ascent - metrics.descent + metrics.leading;
-
-    const TEXT: &str = "OK";
-    let mut pen_x = 0f32;
-    let mut glyphs = [Glyph::default(); TEXT.len()];
-
-    for (ch, glyph) in TEXT.chars().zip(glyphs.iter_mut()) {
-        let gid = charmap.map(ch).unwrap_or_default();
-        let advance = glyph_metrics.advance_width(gid).unwrap_or_default();
-        let x = pen_x;
-        pen_x += advance;
-        *glyph = Glyph {
-            id: gid.to_u32(),
-            x,
-            y: 0.0,
-        };
-    }
+    let (glyphs, pen_x, line_height) = layout_text(&font, "OK", size);
 
     scene.fill(
         Fill::NonZero,
@@ -290,6 +413,84 @@ fn confirm_btn(scene: &mut Scene, elem: ViewElement) {
         .draw(Fill::NonZero, glyphs.into_iter())
 }
 
+// Place the buttons in rows from the top left corner and return the toolbar height
+fn layout_toolbar(elems: &mut [ViewElement], buttons: &[(usize, &str)], width: f64) -> f64 {
+    let font = Font::new(Blob::new(Arc::new(ROBOTO_FONT)), 0);
+    let mut x = TOOLBAR_PADDING;
+    let mut y = TOOLBAR_PADDING;
+    for &(index, label) in buttons {
+        let (_, text_width, _) = layout_text(&font, label, TOOLBAR_FONT_SIZE);
+        let btn_width = text_width as f64 + 2.0 * TOOLBAR_PADDING;
+        if x > TOOLBAR_PADDING && x + btn_width > width {
+            x = TOOLBAR_PADDING;
+            y += TOOLBAR_BTN_HEIGHT + TOOLBAR_PADDING;
+        }
+        elems[index].bound = Rect::new(x, y, x + btn_width, y + TOOLBAR_BTN_HEIGHT);
+        x += btn_width + TOOLBAR_PADDING;
+    }
+    y + TOOLBAR_BTN_HEIGHT + TOOLBAR_PADDING
+}
+
+fn toolbar_btn(scene: &mut Scene, elem: ViewElement, label: &str) {
+    let fill_color = if elem.mouse_enter && elem.active {
+        Color::rgba8(70, 70, 70, 220)
+    } else {
+        Color::BLACK
+    };
+    let text_color = if elem.active {
+        Color::WHITE
+    } else {
+        Color::rgba8(120, 120, 120, 255)
+    };
+    let font = Font::new(Blob::new(Arc::new(ROBOTO_FONT)), 0);
+    let (glyphs, text_width, _) = layout_text(&font, label, TOOLBAR_FONT_SIZE);
+
+    let shape = elem.bound.to_rounded_rect(5.0);
+    scene.fill(Fill::NonZero, Affine::IDENTITY, fill_color, None, &shape);
+    scene.stroke(
+        &Stroke::new(1.5),
+        Affine::IDENTITY,
+        text_color,
+        None,
+        &shape,
+    );
+
+    scene
+        .draw_glyphs(&font)
+        .transform(Affine::translate((
+            elem.bound.x0 + (elem.bound.width() - text_width as f64) / 2.0,
+            elem.bound.y0 + (elem.bound.height() + TOOLBAR_FONT_SIZE as f64 * 0.7) / 2.0,
+        )))
+        .brush(text_color)
+        .font_size(TOOLBAR_FONT_SIZE)
+        .hint(false)
+        .draw(Fill::NonZero, glyphs.into_iter())
+}
+
+// Get the glyphs of a single line of text with its width and line height
+fn layout_text(font: &Font, text: &str, size: f32) -> (Vec<Glyph>, f32, f32) {
+    let font_ref = to_font_ref(font).unwrap();
+    let font_size = vello::skrifa::instance::Size::new(size);
+    let charmap = font_ref.charmap();
+    let glyph_metrics = font_ref.glyph_metrics(font_size, LocationRef::new(&[]));
+    let metrics = font_ref.metrics(font_size, LocationRef::new(&[]));
+    let line_height = metrics.ascent - metrics.descent + metrics.leading;
+
+    let mut pen_x = 0f32;
+    let mut glyphs = Vec::with_capacity(text.len());
+    for ch in text.chars() {
+        let gid = charmap.map(ch).unwrap_or_default();
+        let advance = glyph_metrics.advance_width(gid).unwrap_or_default();
+        glyphs.push(Glyph {
+            id: gid.to_u32(),
+            x: pen_x,
+            y: 0.0,
+        });
+        pen_x += advance;
+    }
+    (glyphs, pen_x, line_height)
+}
+
 fn spinner(scene: &mut Scene, rect: Rect, time: f64) {
     let spinner_fill_color = Color::WHITE;
     let background_fill_color = Color::BLACK;
@@ -424,6 +625,30 @@ impl From<rten_imageproc::RotatedRect> for RotatedRect {
     }
 }
 
+impl std::ops::Mul<RotatedRect> for Affine {
+    type Output = RotatedRect;
+
+    fn mul(self, rect: RotatedRect) -> RotatedRect {
+        RotatedRect {
+            p0: self * rect.p0,
+            p1: self * rect.p1,
+            p2: self * rect.p2,
+            p3: self * rect.p3,
+        }
+    }
+}
+
+impl From<Rect> for RotatedRect {
+    fn from(rect: Rect) -> Self {
+        Self {
+            p0: Point::new(rect.x0, rect.y0),
+            p1: Point::new(rect.x1, rect.y0),
+            p2: Point::new(rect.x1, rect.y1),
+            p3: Point::new(rect.x0, rect.y1),
+        }
+    }
+}
+
 impl From<&RotatedRect> for Rect {
     fn from(value: &RotatedRect) -> Rect {
         Rect::new(value.min_x(), value.min_y(), value.max_x(), value.max_y())
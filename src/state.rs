@@ -1,12 +1,15 @@
+use crate::cli::Settings;
+use crate::clipboard;
+use crate::layout::{self, table::Table};
+use crate::ocr::{self, Line, OcrOptions, OcrResult, Prepared};
+use crate::preprocess::Rotation;
 use crate::scenes::RotatedRect;
 
-use vello::kurbo::{Point, Rect};
-use vello::peniko::Blob;
+use vello::kurbo::{Affine, Point, Rect};
+use vello::peniko::{Blob, Image};
 use vello::Scene;
 
-use ocrs::{ImageSource, OcrEngine, OcrEngineParams};
-use rten::Model;
-
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
@@ -23,7 +26,41 @@ pub const BOTTOM_RIGHT_BTN: usize = 5;
 pub const BOTTOM_LEFT_BTN: usize = 6;
 
 // Text Extraction elements
-pub static EXTRACTED_ELEMS: Mutex<Option<(Vec<RotatedRect>, String)>> = Mutex::new(None);
+pub const EXTRACT_AREA: usize = 0;
+pub const COPY_BTN: usize = 1;
+pub const COPY_TABLE_BTN: usize = 2;
+pub const COPY_PARAGRAPHS_BTN: usize = 3;
+pub const UNDO_COPY_BTN: usize = 4;
+pub const TEXT_MODE_BTN: usize = 5;
+pub const SHOW_ORDER_BTN: usize = 6;
+pub const PREVIEW_BTN: usize = 7;
+pub const ROTATE_LEFT_BTN: usize = 8;
+pub const ROTATE_RIGHT_BTN: usize = 9;
+pub const TEXT_COLOR_BTN: usize = 10;
+// the rectangles of the extracted words follow the toolbar buttons
+pub const TEXT_RECTS: usize = 11;
+
+pub static EXTRACTED_ELEMS: Mutex<Extracted> = Mutex::new(Extracted {
+    latest: None,
+    lines: Vec::new(),
+    result: None,
+});
+
+/// The result of the extraction of the latest capture
+pub struct Extracted {
+    // the start time of the latest extraction, the results of the older ones
+    // are dropped
+    pub latest: Option<Instant>,
+    // the lines of the latest extraction read so far, in reading order
+    pub lines: Vec<Line>,
+    // the result, or the error message, is tagged with the start time of its
+    // extraction, with the image given to the engine when the preprocessing
+    // succeeded
+    pub result: Option<(Instant, Result<OcrResult, String>, Option<Prepared>)>,
+}
+
+// Waits for the result of an area selection started from outside the window
+pub type ResultSender = Sender<Result<OcrResult, String>>;
 
 #[derive(Default, Debug, Clone, Copy)]
 pub struct ViewElement {
@@ -50,6 +87,8 @@ pub struct AppState {
     pub should_exit: bool,
     pub screen_width: f64,
     pub screen_height: f64,
+    pub settings: Settings,
+    pub pending_replies: Vec<ResultSender>,
 }
 
 #[derive(Default, Copy, Clone, PartialEq)]
@@ -74,6 +113,22 @@ pub struct TextExtractData {
     pub window_cleared: bool,
     pub window_created: bool,
     pub text: String,
+    pub result: OcrResult,
+    // the text blocks in reading order, shown numbered on demand
+    pub blocks: Vec<Rect>,
+    pub show_order: bool,
+    // the preprocessed image and its transform to the captured one
+    pub preview: Option<(Image, Affine)>,
+    pub show_preview: bool,
+    // the turns of the capture given to the recognition
+    pub rotation: Rotation,
+    // the color of the text kept in the capture, picked with the eyedropper
+    pub text_color: Option<[u8; 3]>,
+    pub picking_color: bool,
+    // maps the captured image to the window
+    pub image_transform: Affine,
+    // the part of the capture given to the recognition, when its margins were trimmed
+    pub trimmed: Option<Rect>,
     pub rotated_rects: Vec<RotatedRect>,
     pub blob: Blob<u8>,
 }
@@ -81,7 +136,7 @@ pub struct TextExtractData {
 #[derive(Debug, Clone)]
 pub enum PageData {
     AreaSelect(AreaSelectData),
-    TextExtract(TextExtractData),
+    TextExtract(Box<TextExtractData>),
 }
 
 impl Default for PageData {
@@ -144,13 +199,23 @@ impl AppState {
                     state.damaged = true;
                     state.redraw = true;
                     state.page = Page::TextExtract;
-                    state.page_data = Box::new(PageData::TextExtract(TextExtractData {
+                    *state.page_data = PageData::TextExtract(Box::new(TextExtractData {
                         rect: page_data.rect,
                         time: Instant::now(),
                         window_cleared: false,
                         window_created: false,
                         rotated_rects: Vec::new(),
                         text: String::new(),
+                        result: OcrResult::default(),
+                        blocks: Vec::new(),
+                        show_order: false,
+                        preview: None,
+                        show_preview: false,
+                        rotation: state.settings.rotate,
+                        text_color: state.settings.text_color,
+                        picking_color: false,
+                        image_transform: Affine::IDENTITY,
+                        trimmed: None,
                         extracted: false,
                         blob: Blob::new(Arc::new([])),
                     }));
@@ -174,9 +239,147 @@ impl AppState {
                 // return the callbacks vec
                 callbacks
             }
-            Page::TextExtract => vec![|_, _, index| {
-                println!("input on {index}");
-            }],
+            Page::TextExtract => {
+                let mut callbacks: Vec<fn(&mut AppState, &mut View, usize)> = vec![];
+
+                // the extract area, also used for the text rectangles
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !page_data.picking_color {
+                        println!("input on {index}");
+                        return;
+                    }
+                    if !view.elems[index].mouse_press {
+                        return;
+                    }
+                    // the pixel of the capture under the pointer
+                    let point = page_data.image_transform.inverse() * view.mouse_position;
+                    let width = page_data.rect.width().abs() as usize;
+                    let height = page_data.rect.height().abs() as usize;
+                    if point.x < 0.0 || point.y < 0.0 {
+                        return;
+                    }
+                    let (x, y) = (point.x as usize, point.y as usize);
+                    if x >= width || y >= height {
+                        return;
+                    }
+                    let i = (y * width + x) * 4;
+                    let Some(&[r, g, b]) = page_data.blob.data().get(i..i + 3) else {
+                        return;
+                    };
+                    page_data.picking_color = false;
+                    page_data.text_color = Some([r, g, b]);
+                    // show the mask the color leaves
+                    page_data.show_preview = true;
+                    extract_again(&state.settings, page_data, view);
+                });
+
+                // for the copy button
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    copy_on_demand(&state.settings, &page_data.text);
+                });
+
+                // for the copy as table button
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    let table = Table::new(&page_data.result).format(state.settings.table_format);
+                    copy_on_demand(&state.settings, &table);
+                });
+
+                // for the copy as paragraphs button
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    let text = layout::text(&page_data.result, layout::TextMode::Paragraphs);
+                    copy_on_demand(&state.settings, &text);
+                });
+
+                // for the undo copy button
+                callbacks.push(|_, view, index| {
+                    if !view.elems[index].mouse_press {
+                        return;
+                    }
+                    if let Err(err) = clipboard::restore_previous() {
+                        eprintln!("warning: cannot restore the clipboard: {err:#}");
+                    }
+                });
+
+                // for the text mode button, the next copies use the new mode
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    state.settings.text_mode = state.settings.text_mode.next();
+                    page_data.text = layout::text(&page_data.result, state.settings.text_mode);
+                });
+
+                // for the show order button
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    page_data.show_order = !page_data.show_order;
+                });
+
+                // for the preview button
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    page_data.show_preview = !page_data.show_preview;
+                });
+
+                // for the rotate buttons
+                callbacks.push(|state, view, index| rotate_capture(state, view, index, -1));
+                callbacks.push(|state, view, index| rotate_capture(state, view, index, 1));
+
+                // for the text color button, picking a color or clearing it
+                callbacks.push(|state, view, index| {
+                    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+                        return;
+                    };
+                    if !view.elems[index].mouse_press || !page_data.extracted {
+                        return;
+                    }
+                    if page_data.picking_color {
+                        page_data.picking_color = false;
+                    } else if page_data.text_color.is_some() {
+                        page_data.text_color = None;
+                        page_data.show_preview = false;
+                        extract_again(&state.settings, page_data, view);
+                    } else {
+                        page_data.picking_color = true;
+                    }
+                });
+
+                callbacks
+            }
         }
     }
 
@@ -206,31 +409,118 @@ impl AppState {
                 // return the views vec
                 views
             }
-            Page::TextExtract => vec![],
+            Page::TextExtract => {
+                let mut views = vec![];
+                // the extract area
+                views.push(ViewElement {
+                    cursor: CursorIcon::Crosshair,
+                    bound: Rect::new(0.0, 0.0, self.screen_width, self.screen_height),
+                    active: true,
+                    ..Default::default()
+                });
+                // the toolbar buttons are enabled when the text is extracted
+                for _ in COPY_BTN..TEXT_RECTS {
+                    views.push(ViewElement {
+                        cursor: CursorIcon::Pointer,
+                        ..Default::default()
+                    });
+                }
+                views
+            }
         }
     }
 }
 
+// Turn the capture by quarter turns and extract its text again
+fn rotate_capture(state: &mut AppState, view: &mut View, index: usize, quarter_turns: i32) {
+    let PageData::TextExtract(ref mut page_data) = *state.page_data else {
+        return;
+    };
+    if !view.elems[index].mouse_press || !page_data.extracted {
+        return;
+    }
+    page_data.rotation = page_data.rotation.turned(quarter_turns);
+    extract_again(&state.settings, page_data, view);
+}
+
+// Extract the text of the capture again with the options of the extract window
+fn extract_again(settings: &Settings, page_data: &mut TextExtractData, view: &mut View) {
+    page_data.time = Instant::now();
+    page_data.extracted = false;
+    page_data.preview = None;
+    page_data.trimmed = None;
+    page_data.blocks.clear();
+    page_data.rotated_rects.clear();
+    // the toolbar waits for the new result and the old text rectangles go
+    view.elems.truncate(TEXT_RECTS);
+    for elem in &mut view.elems[COPY_BTN..TEXT_RECTS] {
+        elem.active = false;
+    }
+
+    let mut options = settings.ocr_options();
+    options.rotation = page_data.rotation;
+    options.text_color = page_data.text_color;
+    let rect = page_data.rect;
+    let dimensions = (rect.width().abs() as u32, rect.height().abs() as u32);
+    let origin = Point::new(rect.min_x(), rect.min_y());
+    let (blob, time) = (page_data.blob.clone(), page_data.time);
+    std::thread::spawn(move || extract_text(blob, dimensions, origin, time, options));
+}
+
+// Copy from a toolbar button, even when no selection receives the extracted text
+fn copy_on_demand(settings: &Settings, text: &str) {
+    let target = match settings.clipboard {
+        clipboard::ClipboardTarget::None => clipboard::ClipboardTarget::Clipboard,
+        target => target,
+    };
+    if let Err(err) = clipboard::copy_text(target, text, settings.clear_after()) {
+        eprintln!("warning: cannot copy the text: {err:#}");
+    }
+}
+
 // Extract Text from image bytes and write it to the global Cell
-pub fn extract_text(blob: Blob<u8>, dimensions: (u32, u32)) {
-    let detection_model = Model::load_file("assets/text-detection.rten").unwrap();
-    let recognition_model = Model::load_file("assets/text-recognition.rten").unwrap();
-    let engine = OcrEngine::new(OcrEngineParams {
-        detection_model: Some(detection_model),
-        recognition_model: Some(recognition_model),
-        ..Default::default()
-    })
-    .unwrap();
-    let img_source = ImageSource::from_bytes(blob.data(), dimensions).unwrap();
-    let ocr_input = engine.prepare_input(img_source).unwrap();
-    let word_rects = engine.detect_words(&ocr_input).unwrap();
-    // sort the words in lines and then flatten the lines into words again
-    let rects = engine
-        .find_text_lines(&ocr_input, &word_rects)
-        .into_iter()
-        .flatten()
-        .map(RotatedRect::from);
-    let text = engine.get_text(&ocr_input).unwrap();
+pub fn extract_text(
+    blob: Blob<u8>,
+    dimensions: (u32, u32),
+    origin: Point,
+    time: Instant,
+    options: OcrOptions,
+) {
+    {
+        let mut extracted = EXTRACTED_ELEMS.lock().unwrap();
+        if extracted.latest < Some(time) {
+            extracted.latest = Some(time);
+            extracted.lines.clear();
+        }
+    }
+    // the window shows the lines while the next ones are read
+    let on_line = |line: &Line| {
+        let mut extracted = EXTRACTED_ELEMS.lock().unwrap();
+        if extracted.latest == Some(time) {
+            extracted.lines.push(line.clone());
+        }
+    };
+    let prepared = ocr::prepare(blob.data(), dimensions, &options);
+    let result = prepared
+        .as_ref()
+        .map_err(|err| anyhow::anyhow!("{err:#}"))
+        .and_then(|prepared| ocr::recognize_streamed(prepared, &options, on_line))
+        .map(|mut result| {
+            result.origin = origin;
+            result
+        })
+        .map_err(|err| {
+            eprintln!("error: cannot extract the text: {err:#}");
+            format!("cannot extract the text: {err:#}")
+        });
     let mut extracted = EXTRACTED_ELEMS.lock().unwrap();
-    *extracted = Some((rects.collect(), text));
+    // a cancelled extraction finishing late must not replace the result of
+    // the current capture
+    if extracted.latest != Some(time) {
+        return;
+    }
+    if let Ok(result) = &result {
+        ocr::set_last_result(result);
+    }
+    extracted.result = Some((time, result, prepared.ok()));
 }
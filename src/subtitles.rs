@@ -0,0 +1,163 @@
+use anyhow::Result;
+use clap::ValueEnum;
+
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("vtt") => Self::Vtt,
+            _ => Self::Srt,
+        }
+    }
+}
+
+/// Turns the successive texts of a region into cues, a cue starts when its
+/// text appears and ends when the text changes or disappears
+pub struct SubtitleWriter<W: Write> {
+    out: W,
+    format: SubtitleFormat,
+    // the start and the text of the cue being shown
+    open: Option<(Duration, String)>,
+    count: usize,
+}
+
+impl<W: Write> SubtitleWriter<W> {
+    pub fn new(mut out: W, format: SubtitleFormat) -> Result<Self> {
+        if format == SubtitleFormat::Vtt {
+            writeln!(out, "WEBVTT")?;
+            writeln!(out)?;
+        }
+        Ok(Self {
+            out,
+            format,
+            open: None,
+            count: 0,
+        })
+    }
+
+    /// Record the text shown at `time`
+    pub fn update(&mut self, time: Duration, text: &str) -> Result<()> {
+        let text = text.trim();
+        if matches!(self.open, Some((_, ref open)) if open == text) {
+            return Ok(());
+        }
+        self.finish(time)?;
+        if !text.is_empty() {
+            self.open = Some((time, text.to_string()));
+        }
+        Ok(())
+    }
+
+    /// End the cue being shown at `time`
+    pub fn finish(&mut self, time: Duration) -> Result<()> {
+        let Some((start, text)) = self.open.take() else {
+            return Ok(());
+        };
+        self.count += 1;
+        if self.format == SubtitleFormat::Srt {
+            writeln!(self.out, "{}", self.count)?;
+        }
+        writeln!(
+            self.out,
+            "{} --> {}",
+            self.timestamp(start),
+            self.timestamp(time)
+        )?;
+        writeln!(self.out, "{text}")?;
+        writeln!(self.out)?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn timestamp(&self, time: Duration) -> String {
+        let millis = time.as_millis();
+        let separator = match self.format {
+            SubtitleFormat::Srt => ',',
+            SubtitleFormat::Vtt => '.',
+        };
+        format!(
+            "{:02}:{:02}:{:02}{separator}{:03}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cues(format: SubtitleFormat, texts: &[(u64, &str)], end: u64) -> String {
+        let mut out = Vec::new();
+        let mut writer = SubtitleWriter::new(&mut out, format).unwrap();
+        for &(millis, text) in texts {
+            writer.update(Duration::from_millis(millis), text).unwrap();
+        }
+        writer.finish(Duration::from_millis(end)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    const TEXTS: &[(u64, &str)] = &[
+        (0, "Hello"),
+        (500, "Hello"),
+        (1_250, " Hello \n"),
+        (61_001, "Goodbye"),
+        (62_000, ""),
+        (3_723_004, "Later"),
+    ];
+
+    #[test]
+    fn srt() {
+        assert_eq!(
+            cues(SubtitleFormat::Srt, TEXTS, 3_724_000),
+            "1\n00:00:00,000 --> 00:01:01,001\nHello\n\n\
+             2\n00:01:01,001 --> 00:01:02,000\nGoodbye\n\n\
+             3\n01:02:03,004 --> 01:02:04,000\nLater\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt() {
+        assert_eq!(
+            cues(SubtitleFormat::Vtt, TEXTS, 3_724_000),
+            "WEBVTT\n\n\
+             00:00:00,000 --> 00:01:01,001\nHello\n\n\
+             00:01:01,001 --> 00:01:02,000\nGoodbye\n\n\
+             01:02:03,004 --> 01:02:04,000\nLater\n\n"
+                .replace(',', ".")
+        );
+    }
+
+    #[test]
+    fn nothing_shown() {
+        assert_eq!(cues(SubtitleFormat::Srt, &[(0, ""), (1000, " ")], 2000), "");
+        assert_eq!(cues(SubtitleFormat::Vtt, &[], 0), "WEBVTT\n\n");
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            SubtitleFormat::from_path(Path::new("a.VTT")),
+            SubtitleFormat::Vtt
+        );
+        assert_eq!(
+            SubtitleFormat::from_path(Path::new("a.srt")),
+            SubtitleFormat::Srt
+        );
+        assert_eq!(
+            SubtitleFormat::from_path(Path::new("a")),
+            SubtitleFormat::Srt
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::cli::{TriggerArgs, WatchArgs};
+use crate::watch::Watcher;
+
+use anyhow::Result;
+use regex::Regex;
+
+use std::io::Write;
+use std::process::Command;
+use std::time::Duration;
+
+/// A match of the pattern, the whole match first then the groups
+#[derive(Debug, Clone)]
+struct Match {
+    groups: Vec<String>,
+    names: Vec<(String, String)>,
+    text: String,
+}
+
+/// Watch the region and run the command of the rule on the matches of its pattern
+pub fn trigger(args: &WatchArgs, rule: &TriggerArgs) -> Result<()> {
+    watch_matches(args, rule, |found| run(rule, found, &mut std::io::stdout()))
+}
+
+// Call `fire` for each match that stays on screen for the debounce delay
+fn watch_matches(
+    args: &WatchArgs,
+    rule: &TriggerArgs,
+    mut fire: impl FnMut(&Match) -> Result<()>,
+) -> Result<()> {
+    let debounce: Duration = rule.debounce.into();
+    let mut watcher = Watcher::new(args)?;
+    let mut current: Option<Match> = None;
+    // when the current match appeared
+    let mut since = Duration::ZERO;
+    // the match the command already ran for
+    let mut fired: Option<Vec<String>> = None;
+
+    while let Some(frame) = watcher.next_frame()? {
+        if let Some(result) = frame.result {
+            let found = find(&rule.pattern, &result.text());
+            if found.as_ref().map(|m| &m.groups) != current.as_ref().map(|m| &m.groups) {
+                since = frame.time;
+            }
+            current = found;
+        }
+
+        let Some(ref found) = current else {
+            // the pattern must disappear to fire again for the same match
+            fired = None;
+            continue;
+        };
+        if fired.as_ref() == Some(&found.groups) || frame.time - since < debounce {
+            continue;
+        }
+        fire(found)?;
+        fired = Some(found.groups.clone());
+        if rule.once {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn find(pattern: &Regex, text: &str) -> Option<Match> {
+    let captures = pattern.captures(text)?;
+    let groups = captures
+        .iter()
+        .map(|group| group.map_or("", |m| m.as_str()).to_string())
+        .collect();
+    let names = pattern
+        .capture_names()
+        .flatten()
+        .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+        .collect();
+    Some(Match {
+        groups,
+        names,
+        text: text.to_string(),
+    })
+}
+
+fn run(rule: &TriggerArgs, found: &Match, out: &mut impl Write) -> Result<()> {
+    if rule.dry_run {
+        writeln!(out, "{} {:?}", rule.exec, found.groups)?;
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&rule.exec).arg("screen-ocr");
+        command.args(&found.groups);
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&rule.exec);
+        command
+    };
+
+    command.env("SCREEN_OCR_MATCH", &found.groups[0]);
+    command.env("SCREEN_OCR_TEXT", &found.text);
+    for (i, group) in found.groups.iter().enumerate().skip(1) {
+        command.env(format!("SCREEN_OCR_GROUP_{i}"), group);
+    }
+    for (name, value) in &found.names {
+        command.env(format!("SCREEN_OCR_GROUP_{}", name.to_uppercase()), value);
+    }
+
+    let status = command.status()?;
+    if !status.success() {
+        eprintln!("warning: `{}` exited with {status}", rule.exec);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn rule(debounce: u64, once: bool) -> TriggerArgs {
+        TriggerArgs {
+            pattern: Regex::new(r"Code (\d+)").unwrap(),
+            exec: "notify-send".to_string(),
+            debounce: Duration::from_secs(debounce).into(),
+            once,
+            dry_run: true,
+        }
+    }
+
+    // The groups of the matches fired while replaying the frames, one second apart
+    fn fired(name: &str, texts: &[Option<&str>], rule: &TriggerArgs) -> Vec<Vec<String>> {
+        let args = WatchArgs {
+            region: None,
+            frames: Some(fixtures::frames(name, texts)),
+            interval: Duration::from_secs(1).into(),
+            threshold: 0.01,
+        };
+        let mut fired = vec![];
+        watch_matches(&args, rule, |found| {
+            fired.push(found.groups.clone());
+            Ok(())
+        })
+        .unwrap();
+        fired
+    }
+
+    fn groups(code: &str) -> Vec<String> {
+        vec![format!("Code {code}"), code.to_string()]
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn debounce() {
+        // the match disappears before the debounce ends
+        let texts = [Some("Code 1234"), Some("Code 1234"), None, None];
+        assert!(fired("debounce-short", &texts, &rule(2, false)).is_empty());
+
+        let texts = [
+            Some("Code 1234"),
+            Some("Code 1234"),
+            Some("Code 1234"),
+            None,
+        ];
+        assert_eq!(
+            fired("debounce-long", &texts, &rule(2, false)),
+            [groups("1234")]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn repeat() {
+        // a match still on screen fires once, it fires again after it
+        // disappeared or for another match
+        let texts = [
+            Some("Code 1234"),
+            Some("Code 1234"),
+            None,
+            Some("Code 1234"),
+            Some("Code 5678"),
+        ];
+        assert_eq!(
+            fired("repeat", &texts, &rule(0, false)),
+            [groups("1234"), groups("1234"), groups("5678")]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn once() {
+        let texts = [Some("Code 1234"), None, Some("Code 5678")];
+        assert_eq!(fired("once", &texts, &rule(0, true)), [groups("1234")]);
+    }
+
+    #[test]
+    fn dry_run() {
+        let found = find(&rule(0, false).pattern, "Your Code 1234 expires").unwrap();
+        let mut out = vec![];
+        run(&rule(0, false), &found, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "notify-send [\"Code 1234\", \"1234\"]\n"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use crate::cli::WatchArgs;
+use crate::watch::Watcher;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+
+use std::time::Duration;
+
+/// Watch the region until its text contains the expected text, the last
+/// text seen is printed either way
+pub fn wait_for(args: &WatchArgs, text: &str, regex: bool, timeout: Duration) -> Result<()> {
+    let pattern = if regex {
+        Regex::new(text)?
+    } else {
+        Regex::new(&regex::escape(text))?
+    };
+    let mut watcher = Watcher::new(args)?;
+    let mut last = String::new();
+    // the frames captured after the timeout don't count
+    while let Some(frame) = watcher.next_frame_before(timeout)? {
+        if let Some(result) = frame.result {
+            last = result.text();
+            if pattern.is_match(&last) {
+                println!("{last}");
+                return Ok(());
+            }
+        }
+    }
+    println!("{last}");
+    bail!(
+        "`{text}` did not appear within {}",
+        humantime::format_duration(timeout)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // Wait two seconds for the text in frames replayed one second apart
+    fn wait(name: &str, texts: &[Option<&str>]) -> Result<()> {
+        let args = WatchArgs {
+            region: None,
+            frames: Some(fixtures::frames(name, texts)),
+            interval: Duration::from_secs(1).into(),
+            threshold: 0.01,
+        };
+        wait_for(&args, "Ready", false, Duration::from_secs(2))
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn early_match() {
+        assert!(wait("early", &[None, Some("Ready")]).is_ok());
+    }
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn late_match() {
+        let err = wait("late", &[None, None, None, Some("Ready")]).unwrap_err();
+        assert_eq!(err.to_string(), "`Ready` did not appear within 2s");
+    }
+}
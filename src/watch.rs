@@ -0,0 +1,243 @@
+use crate::capture::Capturer;
+use crate::cli::WatchArgs;
+use crate::ocr::{self, OcrResult};
+use crate::subtitles::{SubtitleFormat, SubtitleWriter};
+
+use anyhow::{bail, Result};
+use image::RgbaImage;
+use vello::kurbo::{Point, Rect};
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where the watched pixels come from
+enum Source {
+    Screen(Box<Capturer>, Rect),
+    // the remaining image files, replayed one per interval
+    Frames(std::vec::IntoIter<PathBuf>, Option<Rect>),
+}
+
+/// A capture of the watched region
+pub struct Frame {
+    // the time since the start of the watch
+    pub time: Duration,
+    // the recognized text, `None` when the pixels did not change
+    pub result: Option<OcrResult>,
+}
+
+/// Captures a region at a fixed interval and recognizes it again only when
+/// its pixels changed noticeably
+pub struct Watcher {
+    source: Source,
+    interval: Duration,
+    threshold: f64,
+    // the pixels of the last recognized capture
+    last_frame: Option<Vec<u8>>,
+    start: Instant,
+    ticks: u32,
+}
+
+impl Watcher {
+    pub fn new(args: &WatchArgs) -> Result<Self> {
+        let source = match (&args.frames, args.region) {
+            (Some(dir), region) => {
+                let mut paths = std::fs::read_dir(dir)?
+                    .map(|entry| Ok(entry?.path()))
+                    .collect::<Result<Vec<_>>>()?;
+                paths.retain(|path| path.is_file());
+                paths.sort();
+                Source::Frames(paths.into_iter(), region)
+            }
+            (None, Some(region)) => Source::Screen(Box::new(Capturer::new()?), region),
+            (None, None) => bail!("a region or frames to replay are required"),
+        };
+        Ok(Self {
+            source,
+            interval: args.interval.into(),
+            threshold: args.threshold,
+            last_frame: None,
+            start: Instant::now(),
+            ticks: 0,
+        })
+    }
+
+    /// Wait for the next tick and capture the region, returns `None` when
+    /// there are no more frames to replay
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        self.next_frame_before(Duration::MAX)
+    }
+
+    /// Like `next_frame`, without waiting past the deadline counted from the
+    /// start of the watch, returns `None` when the next frame would be later
+    pub fn next_frame_before(&mut self, deadline: Duration) -> Result<Option<Frame>> {
+        let tick = self.interval * self.ticks;
+        self.ticks += 1;
+
+        let (time, pixels, dimensions, origin) = match self.source {
+            Source::Screen(ref capturer, rect) => {
+                let elapsed = self.start.elapsed();
+                let wake = tick.min(deadline);
+                if wake > elapsed {
+                    std::thread::sleep(wake - elapsed);
+                }
+                // a slow recognition delays the captures past their tick
+                let time = self.start.elapsed();
+                if time > deadline {
+                    return Ok(None);
+                }
+                let pixels = capturer.capture(rect)?.data().to_vec();
+                let dimensions = (rect.width() as u32, rect.height() as u32);
+                (time, pixels, dimensions, Point::new(rect.x0, rect.y0))
+            }
+            Source::Frames(ref mut paths, region) => {
+                let Some(path) = paths.next().filter(|_| tick <= deadline) else {
+                    return Ok(None);
+                };
+                let image = crop(image::open(&path)?.to_rgba8(), region);
+                let dimensions = image.dimensions();
+                let origin = region.map_or(Point::ZERO, |r| Point::new(r.x0, r.y0));
+                // the replayed frames were recorded one interval apart
+                (tick, image.into_raw(), dimensions, origin)
+            }
+        };
+
+        if let Some(ref last_frame) = self.last_frame {
+            if difference(last_frame, &pixels) < self.threshold {
+                return Ok(Some(Frame { time, result: None }));
+            }
+        }
+
+        let mut result = ocr::recognize(&pixels, dimensions)?;
+        result.origin = origin;
+        self.last_frame = Some(pixels);
+        Ok(Some(Frame {
+            time,
+            result: Some(result),
+        }))
+    }
+}
+
+fn crop(image: RgbaImage, region: Option<Rect>) -> RgbaImage {
+    let Some(rect) = region else {
+        return image;
+    };
+    let (x, y) = (rect.x0 as u32, rect.y0 as u32);
+    image::imageops::crop_imm(&image, x, y, rect.width() as u32, rect.height() as u32).to_image()
+}
+
+// The mean difference of the pixel channels, from 0 to 1
+fn difference(a: &[u8], b: &[u8]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 1.0;
+    }
+    let sum: u64 = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .map(|(p, q)| (0..3).map(|c| p[c].abs_diff(q[c]) as u64).sum::<u64>())
+        .sum();
+    sum as f64 / (a.len() / 4 * 3 * 255) as f64
+}
+
+/// Print the lines of text that appear in the region with their timestamp,
+/// and write them as subtitle cues when a subtitle file is given
+pub fn watch(
+    args: &WatchArgs,
+    output: Option<&Path>,
+    subtitles: Option<(&Path, SubtitleFormat)>,
+) -> Result<()> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut cues = match subtitles {
+        Some((path, format)) => Some(SubtitleWriter::new(
+            BufWriter::new(File::create(path)?),
+            format,
+        )?),
+        None => None,
+    };
+
+    let mut watcher = Watcher::new(args)?;
+    let mut previous = String::new();
+    let mut end = Duration::ZERO;
+    while let Some(frame) = watcher.next_frame()? {
+        end = frame.time;
+        let Some(result) = frame.result else {
+            continue;
+        };
+        let text = result.text();
+        if let Some(ref mut cues) = cues {
+            cues.update(frame.time, &text)?;
+        }
+        if text == previous {
+            continue;
+        }
+        let timestamp = humantime::format_rfc3339_seconds(SystemTime::now());
+        for line in text.lines() {
+            // the lines still shown since the last text are not repeated
+            if line.trim().is_empty() || previous.lines().any(|p| p == line) {
+                continue;
+            }
+            writeln!(out, "{timestamp} {line}")?;
+        }
+        out.flush()?;
+        previous = text;
+    }
+
+    // the last cue lasts until the end of the replayed frames
+    if let Some(ref mut cues) = cues {
+        cues.finish(end + watcher.interval)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Style};
+
+    #[test]
+    #[ignore = "needs the OCR models"]
+    fn replay_frames() {
+        let dir = fixtures::temp_dir("frames");
+        let frames = dir.join("frames");
+        std::fs::create_dir(&frames).unwrap();
+        let hello = fixtures::render(&["Hello world"], Style::default());
+        let (width, height) = hello.dimensions();
+        let blank = RgbaImage::from_pixel(width, height, image::Rgba([255; 4]));
+        let mut goodbye = blank.clone();
+        let text = fixtures::render(&["Goodbye"], Style::default());
+        image::imageops::overlay(&mut goodbye, &text, 0, 0);
+        for (index, image) in [&hello, &hello, &goodbye, &blank].into_iter().enumerate() {
+            image.save(frames.join(format!("{index:03}.png"))).unwrap();
+        }
+
+        let args = WatchArgs {
+            region: None,
+            frames: Some(frames),
+            interval: Duration::from_secs(1).into(),
+            threshold: 0.01,
+        };
+        let (output, subtitles) = (dir.join("lines.txt"), dir.join("cues.srt"));
+        watch(
+            &args,
+            Some(&output),
+            Some((&subtitles, SubtitleFormat::Srt)),
+        )
+        .unwrap();
+
+        let lines = std::fs::read_to_string(output).unwrap();
+        let lines: Vec<_> = lines
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(lines, ["Hello world", "Goodbye"]);
+        assert_eq!(
+            std::fs::read_to_string(subtitles).unwrap(),
+            "1\n00:00:00,000 --> 00:00:02,000\nHello world\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nGoodbye\n\n"
+        );
+    }
+}